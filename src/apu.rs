@@ -0,0 +1,498 @@
+mod dmc;
+mod frame_counter;
+mod mixer;
+mod noise;
+mod pulse;
+mod triangle;
+
+use crate::{
+    audio::{self, Audio},
+    region::Region,
+};
+use bitflags::bitflags;
+
+use self::{
+    dmc::Dmc,
+    frame_counter::FrameCounter,
+    noise::Noise,
+    pulse::{Pulse, PulseChannel},
+    triangle::Triangle,
+};
+
+const PULSE_1_START: u16 = 0x4000;
+const PULSE_1_END: u16 = 0x4003;
+const PULSE_2_START: u16 = 0x4004;
+const PULSE_2_END: u16 = 0x4007;
+const TRIANGLE_START: u16 = 0x4008;
+const TRIANGLE_END: u16 = 0x400B;
+const NOISE_START: u16 = 0x400C;
+const NOISE_END: u16 = 0x400F;
+const DMC_START: u16 = 0x4010;
+const DMC_END: u16 = 0x4013;
+pub const CHANNEL_REGISTERS_START: u16 = 0x4000;
+pub const CHANNEL_REGISTERS_END: u16 = 0x4013;
+
+/**
+ * Length counter values, indexed by the top 5 bits of a channel's length register
+ */
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+bitflags! {
+
+    // 7  bit  0
+    // ---- ----
+    // IF-D NT21
+    // || | ||||
+    // || | |||+- Pulse 1 enabled / length counter > 0
+    // || | ||+-- Pulse 2 enabled / length counter > 0
+    // || | |+--- Triangle enabled / length counter > 0
+    // || | +---- Noise enabled / length counter > 0
+    // || +------ DMC enabled / bytes remaining > 0
+    // |+-------- Frame interrupt (read only)
+    // +--------- DMC interrupt (read only)
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct StatusRegister: u8 {
+       const DMC_INTERRUPT      = 0b1000_0000;
+       const FRAME_INTERRUPT    = 0b0100_0000;
+       const DMC                = 0b0001_0000;
+       const NOISE              = 0b0000_1000;
+       const TRIANGLE           = 0b0000_0100;
+       const PULSE_2            = 0b0000_0010;
+       const PULSE_1            = 0b0000_0001;
+   }
+}
+
+/**
+ * The audio processing unit. Its channels' timers are clocked from CPU cycles,
+ * and their envelopes, sweeps and length counters by the frame counter.
+ * Their mixed output is sampled into audio every cycle.
+ */
+#[derive(Debug, PartialEq)]
+pub struct Apu {
+    pub pulse_1: Pulse,
+    pub pulse_2: Pulse,
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc: Dmc,
+    pub frame_counter: FrameCounter,
+    pub audio: Audio,
+
+    // The pulse timers are clocked every other CPU cycle, and the others every cycle
+    odd_cycle: bool,
+}
+
+impl Apu {
+    pub fn new(region: Region) -> Self {
+        Apu {
+            pulse_1: Pulse::new(PulseChannel::One),
+            pulse_2: Pulse::new(PulseChannel::Two),
+            triangle: Triangle::new(),
+            noise: Noise::new(region),
+            dmc: Dmc::new(region),
+            frame_counter: FrameCounter::new(region),
+            audio: Audio::new(region.cpu_clock_rate(), audio::DEFAULT_SAMPLE_RATE),
+            odd_cycle: false,
+        }
+    }
+
+    /**
+     * Writes to the channel registers, bus::[$4000, $4013]
+     */
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            PULSE_1_START..=PULSE_1_END => self.pulse_1.write_register(address, value),
+            PULSE_2_START..=PULSE_2_END => self.pulse_2.write_register(address, value),
+            TRIANGLE_START..=TRIANGLE_END => self.triangle.write_register(address, value),
+            NOISE_START..=NOISE_END => self.noise.write_register(address, value),
+            DMC_START..=DMC_END => self.dmc.write_register(address, value),
+            _ => panic!("Attempt to write to invalid APU address: {:04X}", address),
+        }
+    }
+
+    /**
+     * Reads from bus::$4015
+     * Acknowledges the frame interrupt
+     */
+    pub fn read_status(&mut self) -> u8 {
+        let mut status = StatusRegister::empty();
+        status.set(StatusRegister::DMC_INTERRUPT, self.dmc.irq);
+        status.set(StatusRegister::FRAME_INTERRUPT, self.frame_counter.irq);
+        self.frame_counter.irq = false;
+        status.set(StatusRegister::DMC, self.dmc.is_active());
+        status.set(StatusRegister::NOISE, self.noise.length_counter.is_active());
+        status.set(
+            StatusRegister::TRIANGLE,
+            self.triangle.length_counter.is_active(),
+        );
+        status.set(
+            StatusRegister::PULSE_2,
+            self.pulse_2.length_counter.is_active(),
+        );
+        status.set(
+            StatusRegister::PULSE_1,
+            self.pulse_1.length_counter.is_active(),
+        );
+        status.bits()
+    }
+
+    /**
+     * Writes to bus::$4015
+     * Enables or disables the channels, and acknowledges the DMC interrupt
+     */
+    pub fn write_status(&mut self, value: u8) {
+        let status = StatusRegister::from_bits_truncate(value);
+        self.pulse_1
+            .length_counter
+            .set_enabled(status.contains(StatusRegister::PULSE_1));
+        self.pulse_2
+            .length_counter
+            .set_enabled(status.contains(StatusRegister::PULSE_2));
+        self.triangle
+            .length_counter
+            .set_enabled(status.contains(StatusRegister::TRIANGLE));
+        self.noise
+            .length_counter
+            .set_enabled(status.contains(StatusRegister::NOISE));
+        self.dmc.set_enabled(status.contains(StatusRegister::DMC));
+        self.dmc.irq = false;
+    }
+
+    /**
+     * Writes to bus::$4017
+     */
+    pub fn write_frame_counter(&mut self, value: u8) {
+        self.frame_counter.write(value, self.odd_cycle);
+    }
+
+    /**
+     * Whether the APU is asserting the CPU's IRQ line
+     */
+    pub fn irq(&self) -> bool {
+        self.dmc.irq || self.frame_counter.irq
+    }
+
+    /**
+     * Advances the APU by the given number of CPU cycles
+     */
+    pub fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.triangle.clock_timer();
+            self.noise.clock_timer();
+            self.dmc.clock_timer();
+            if self.odd_cycle {
+                self.pulse_1.clock_timer();
+                self.pulse_2.clock_timer();
+            }
+            self.odd_cycle = !self.odd_cycle;
+
+            let clocks = self.frame_counter.clock();
+            if clocks.quarter_frame {
+                self.clock_quarter_frame();
+            }
+            if clocks.half_frame {
+                self.clock_half_frame();
+            }
+
+            self.audio.push(self.output());
+        }
+    }
+
+    /**
+     * The channels' mixed output amplitude, [0, 1)
+     */
+    pub fn output(&self) -> f32 {
+        mixer::mix(
+            self.pulse_1.output(),
+            self.pulse_2.output(),
+            self.triangle.output(),
+            self.noise.output(),
+            self.dmc.output(),
+        )
+    }
+
+    /**
+     * Clocks the envelopes and the triangle's linear counter
+     */
+    fn clock_quarter_frame(&mut self) {
+        self.pulse_1.envelope.clock();
+        self.pulse_2.envelope.clock();
+        self.triangle.clock_linear_counter();
+        self.noise.envelope.clock();
+    }
+
+    /**
+     * Clocks the length counters and sweeps
+     */
+    fn clock_half_frame(&mut self) {
+        self.pulse_1.length_counter.clock();
+        self.pulse_1.clock_sweep();
+        self.pulse_2.length_counter.clock();
+        self.pulse_2.clock_sweep();
+        self.triangle.length_counter.clock();
+        self.noise.length_counter.clock();
+    }
+}
+
+/**
+ * Volume control for the pulse and noise channels. Outputs either a constant
+ * volume, or a level that decays from 15 to 0 once every period + 1 quarter
+ * frames, optionally looping back to 15.
+ *
+ * 7  bit  0
+ * ---- ----
+ * --LC VVVV
+ *   || ||||
+ *   || ++++- Constant volume, or decay period
+ *   |+------ Constant volume (0: decay; 1: constant)
+ *   +------- Loop the decay. Also halts the channel's length counter.
+ */
+#[derive(Debug, Default, PartialEq, Eq, Hash)]
+pub struct Envelope {
+    start: bool,
+    looping: bool,
+    constant_volume: bool,
+    volume: u8,
+    divider: u8,
+    decay_level: u8,
+}
+
+impl Envelope {
+    pub fn write(&mut self, value: u8) {
+        self.looping = value & 0b0010_0000 != 0;
+        self.constant_volume = value & 0b0001_0000 != 0;
+        self.volume = value & 0b0000_1111;
+    }
+
+    /**
+     * Restarts the decay on the next quarter frame. Happens on writes to the
+     * channel's length register.
+     */
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay_level = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay_level > 0 {
+                self.decay_level -= 1;
+            } else if self.looping {
+                self.decay_level = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.decay_level
+        }
+    }
+}
+
+/**
+ * Silences a channel once a given number of half frames have passed.
+ * Disabled channels have their counter held at 0.
+ */
+#[derive(Debug, Default, PartialEq, Eq, Hash)]
+pub struct LengthCounter {
+    enabled: bool,
+    halted: bool,
+    counter: u8,
+}
+
+impl LengthCounter {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    pub fn set_halted(&mut self, halted: bool) {
+        self.halted = halted;
+    }
+
+    /**
+     * Loads the counter from LENGTH_TABLE. Ignored while the channel is disabled.
+     */
+    pub fn load(&mut self, index: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[index as usize];
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if self.counter > 0 && !self.halted {
+            self.counter -= 1;
+        }
+    }
+
+    /**
+     * Whether the channel is still sounding
+     */
+    pub fn is_active(&self) -> bool {
+        self.counter > 0
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        apu::{Apu, Envelope, LengthCounter},
+        region::Region,
+    };
+
+    #[test]
+    fn test_status() {
+        let mut apu = Apu::new(Region::Ntsc);
+        // Length counter loads are ignored while the channel is disabled
+        apu.write_register(0x4003, 0b0000_1000);
+        assert_eq!(apu.read_status(), 0);
+
+        apu.write_status(0b0001_1111);
+        for address in [0x4003, 0x4007, 0x400B, 0x400F] {
+            apu.write_register(address, 0b0000_1000);
+        }
+        assert_eq!(apu.read_status(), 0b0001_1111);
+
+        apu.write_status(0b0000_0101);
+        assert_eq!(apu.read_status(), 0b0000_0101);
+    }
+
+    #[test]
+    fn test_frame_irq() {
+        let mut apu = Apu::new(Region::Ntsc);
+        apu.tick(29828);
+        assert!(apu.irq());
+        apu.tick(2);
+
+        // Reading $4015 acknowledges it
+        assert_eq!(apu.read_status(), 0b0100_0000);
+        assert!(!apu.irq());
+        assert_eq!(apu.read_status(), 0);
+    }
+
+    #[test]
+    fn test_frame_counter_clocks_length_counters() {
+        let mut apu = Apu::new(Region::Ntsc);
+        apu.write_status(0b0000_0001);
+        // Length 2
+        apu.write_register(0x4003, 0b0001_1000);
+
+        apu.tick(14913);
+        assert_eq!(apu.read_status() & 1, 1);
+        apu.tick(29829 - 14913);
+        assert_eq!(apu.read_status() & 1, 0);
+    }
+
+    #[test]
+    fn test_audio_samples() {
+        let mut apu = Apu::new(Region::Ntsc);
+        apu.write_status(0b0000_0001);
+        apu.write_register(0x4000, 0b1011_1111);
+        apu.write_register(0x4002, 0xFD);
+        apu.write_register(0x4003, 0b0000_1000);
+
+        // A frame's worth of samples at 44.1kHz
+        apu.tick(29830);
+        let samples = apu.audio.take_samples();
+        assert_eq!(samples.len(), 735);
+        assert!(samples.iter().any(|&sample| sample > 0.01));
+    }
+
+    #[test]
+    fn test_status_acknowledges_dmc_irq() {
+        let mut apu = Apu::new(Region::Ntsc);
+        apu.write_register(0x4010, 0b1000_0000);
+        apu.write_status(0b0001_0000);
+        apu.dmc.load_sample(0);
+        assert!(apu.irq());
+        assert_eq!(apu.read_status(), 0b1000_0000);
+
+        apu.write_status(0);
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn test_envelope_decay() {
+        let mut envelope = Envelope::default();
+        // Decay period 1
+        envelope.write(0b0000_0001);
+        envelope.restart();
+
+        envelope.clock();
+        assert_eq!(envelope.output(), 15);
+        envelope.clock();
+        assert_eq!(envelope.output(), 15);
+        envelope.clock();
+        assert_eq!(envelope.output(), 14);
+
+        for _ in 0..28 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.output(), 0);
+        envelope.clock();
+        envelope.clock();
+        assert_eq!(envelope.output(), 0);
+    }
+
+    #[test]
+    fn test_envelope_loop() {
+        let mut envelope = Envelope::default();
+        // Loop, decay period 0
+        envelope.write(0b0010_0000);
+        envelope.restart();
+
+        for _ in 0..16 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.output(), 0);
+        envelope.clock();
+        assert_eq!(envelope.output(), 15);
+    }
+
+    #[test]
+    fn test_envelope_constant_volume() {
+        let mut envelope = Envelope::default();
+        envelope.write(0b0001_0111);
+        envelope.restart();
+        envelope.clock();
+        envelope.clock();
+        assert_eq!(envelope.output(), 7);
+    }
+
+    #[test]
+    fn test_length_counter() {
+        let mut length_counter = LengthCounter::default();
+        length_counter.load(1);
+        assert!(!length_counter.is_active());
+
+        length_counter.set_enabled(true);
+        length_counter.load(3);
+        length_counter.clock();
+        assert!(length_counter.is_active());
+        length_counter.clock();
+        assert!(!length_counter.is_active());
+
+        length_counter.load(3);
+        length_counter.set_halted(true);
+        length_counter.clock();
+        length_counter.clock();
+        assert!(length_counter.is_active());
+
+        length_counter.set_enabled(false);
+        assert!(!length_counter.is_active());
+    }
+}
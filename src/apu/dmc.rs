@@ -0,0 +1,274 @@
+use crate::region::Region;
+
+/**
+ * Timer periods in CPU cycles, indexed by the low 4 bits of $4010
+ */
+const NTSC_RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const PAL_RATE_TABLE: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+const SAMPLE_ADDRESS_START: u16 = 0xC000;
+// Sample addresses wrap around to $8000 after $FFFF
+const SAMPLE_ADDRESS_WRAP: u16 = 0x8000;
+const MAX_OUTPUT_LEVEL: u8 = 127;
+
+/**
+ * The delta modulation channel. Plays 1-bit delta encoded samples read from
+ * PRG memory, moving its 7-bit output level up or down by 2 for each bit.
+ * Registers:
+ *
+ * $4010: IL-- RRRR - IRQ enabled, loop, rate index
+ * $4011: -DDD DDDD - Output level
+ * $4012: AAAA AAAA - Sample address, $C000 + A * 64
+ * $4013: LLLL LLLL - Sample length, L * 16 + 1 bytes
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Dmc {
+    rate_table: &'static [u16; 16],
+    irq_enabled: bool,
+    looping: bool,
+    timer_period: u16,
+    timer: u16,
+    output_level: u8,
+
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+
+    /**
+     * Set when a sample ends without looping, if IRQs are enabled
+     */
+    pub irq: bool,
+}
+
+impl Dmc {
+    pub fn new(region: Region) -> Self {
+        let rate_table = match region {
+            Region::Ntsc | Region::Dendy => &NTSC_RATE_TABLE,
+            Region::Pal => &PAL_RATE_TABLE,
+        };
+
+        Dmc {
+            rate_table,
+            irq_enabled: false,
+            looping: false,
+            timer_period: rate_table[0],
+            timer: 0,
+            output_level: 0,
+
+            sample_address: SAMPLE_ADDRESS_START,
+            sample_length: 1,
+            current_address: SAMPLE_ADDRESS_START,
+            bytes_remaining: 0,
+            sample_buffer: None,
+
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+
+            irq: false,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address & 0b11 {
+            0 => {
+                self.irq_enabled = value & 0b1000_0000 != 0;
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+                self.looping = value & 0b0100_0000 != 0;
+                self.timer_period = self.rate_table[(value & 0b1111) as usize];
+            }
+            1 => self.output_level = value & 0b0111_1111,
+            2 => self.sample_address = SAMPLE_ADDRESS_START + value as u16 * 64,
+            _ => self.sample_length = value as u16 * 16 + 1,
+        }
+    }
+
+    /**
+     * Enabling the channel starts the sample, unless it's already playing.
+     * Disabling it stops the sample once the sample buffer has been played.
+     */
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    /**
+     * Whether the sample still has bytes left to read
+     */
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    /**
+     * The address the DMC wants to read the next sample byte from, if the
+     * sample buffer is empty and the sample isn't over
+     */
+    pub fn pending_read(&self) -> Option<u16> {
+        (self.sample_buffer.is_none() && self.bytes_remaining > 0).then_some(self.current_address)
+    }
+
+    /**
+     * Fills the sample buffer with the byte read from pending_read()
+     */
+    pub fn load_sample(&mut self, value: u8) {
+        self.sample_buffer = Some(value);
+        self.current_address = match self.current_address.checked_add(1) {
+            Some(address) => address,
+            None => SAMPLE_ADDRESS_WRAP,
+        };
+
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    /**
+     * Clocked every CPU cycle. Plays one bit of the sample per timer period,
+     * and moves on to the sample buffer every 8 bits.
+     */
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period - 1;
+
+        if !self.silence {
+            if self.shift_register & 1 != 0 {
+                if self.output_level <= MAX_OUTPUT_LEVEL - 2 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.shift_register = sample;
+                    self.silence = false;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    /**
+     * Current level, [0, 127]
+     */
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{apu::dmc::Dmc, region::Region};
+
+    /**
+     * Plays a whole byte: 8 timer periods at the fastest rate
+     */
+    fn play_byte(dmc: &mut Dmc) {
+        for _ in 0..8 * 54 {
+            dmc.clock_timer();
+        }
+    }
+
+    #[test]
+    fn test_dmc_sample_reads() {
+        let mut dmc = Dmc::new(Region::Ntsc);
+        dmc.write_register(0x4012, 0xFF);
+        dmc.write_register(0x4013, 0x04);
+        assert_eq!(dmc.pending_read(), None);
+
+        dmc.set_enabled(true);
+        assert_eq!(dmc.pending_read(), Some(0xFFC0));
+        for _ in 0..64 {
+            dmc.load_sample(0);
+            dmc.sample_buffer = None;
+        }
+        // Wraps around to $8000
+        assert_eq!(dmc.pending_read(), Some(0x8000));
+        dmc.load_sample(0);
+        dmc.sample_buffer = None;
+        assert_eq!(dmc.pending_read(), None);
+        assert!(!dmc.is_active());
+    }
+
+    #[test]
+    fn test_dmc_output_level() {
+        let mut dmc = Dmc::new(Region::Ntsc);
+        dmc.write_register(0x4010, 0x0F);
+        dmc.write_register(0x4011, 64);
+        dmc.set_enabled(true);
+        dmc.load_sample(0b0000_0111);
+
+        // The first byte starts playing once the current (silent) byte is done
+        play_byte(&mut dmc);
+        assert_eq!(dmc.output(), 64);
+        play_byte(&mut dmc);
+        assert_eq!(dmc.output(), 64 + 3 * 2 - 5 * 2);
+
+        dmc.write_register(0x4011, 126);
+        dmc.set_enabled(true);
+        dmc.load_sample(0xFF);
+        play_byte(&mut dmc);
+        play_byte(&mut dmc);
+        assert_eq!(dmc.output(), 126);
+    }
+
+    #[test]
+    fn test_dmc_irq() {
+        let mut dmc = Dmc::new(Region::Ntsc);
+        dmc.write_register(0x4010, 0x80);
+        dmc.set_enabled(true);
+        dmc.load_sample(0);
+        assert!(dmc.irq);
+
+        // Disabling IRQs acknowledges it
+        dmc.write_register(0x4010, 0x00);
+        assert!(!dmc.irq);
+    }
+
+    #[test]
+    fn test_dmc_loop() {
+        let mut dmc = Dmc::new(Region::Ntsc);
+        dmc.write_register(0x4010, 0xC0);
+        dmc.write_register(0x4012, 0x01);
+        dmc.set_enabled(true);
+        dmc.load_sample(0);
+        dmc.sample_buffer = None;
+
+        assert!(!dmc.irq);
+        assert_eq!(dmc.pending_read(), Some(0xC040));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::region::Region;
+
+/**
+ * CPU cycles after the sequence starts that each of the first 4 steps happen on
+ */
+const NTSC_STEP_CYCLES: [u32; 4] = [7457, 14913, 22371, 29829];
+const PAL_STEP_CYCLES: [u32; 4] = [8313, 16627, 24939, 33253];
+/**
+ * CPU cycle the last step of the 5-step sequence happens on
+ */
+const NTSC_FIFTH_STEP_CYCLE: u32 = 37281;
+const PAL_FIFTH_STEP_CYCLE: u32 = 41565;
+
+/**
+ * Which of the channels' units should be clocked on a cycle
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FrameClocks {
+    /**
+     * Envelopes and the triangle's linear counter
+     */
+    pub quarter_frame: bool,
+    /**
+     * Length counters and sweeps
+     */
+    pub half_frame: bool,
+}
+
+impl FrameClocks {
+    const NONE: FrameClocks = FrameClocks {
+        quarter_frame: false,
+        half_frame: false,
+    };
+    const QUARTER: FrameClocks = FrameClocks {
+        quarter_frame: true,
+        half_frame: false,
+    };
+    const BOTH: FrameClocks = FrameClocks {
+        quarter_frame: true,
+        half_frame: true,
+    };
+}
+
+/**
+ * The frame sequencer. Clocks the channels' units about 4 times a frame
+ * (240Hz on NTSC), in one of two modes, set by $4017:
+ *
+ * 7  bit  0
+ * ---- ----
+ * MI-- ----
+ * ||
+ * |+-------- IRQ inhibit. Setting it also clears the frame interrupt flag.
+ * +--------- Mode (0: 4-step; 1: 5-step)
+ *
+ * The 4-step sequence raises the frame interrupt on its last step. The 5-step
+ * sequence never does, and has an empty fourth step, so it's slower.
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct FrameCounter {
+    step_cycles: &'static [u32; 4],
+    fifth_step_cycle: u32,
+
+    five_step_mode: bool,
+    irq_inhibit: bool,
+    /**
+     * The frame interrupt flag. Cleared by reading $4015.
+     */
+    pub irq: bool,
+
+    cycle: u32,
+    /**
+     * CPU cycles until a $4017 write restarts the sequence
+     */
+    reset_delay: Option<u8>,
+}
+
+impl FrameCounter {
+    pub fn new(region: Region) -> Self {
+        let (step_cycles, fifth_step_cycle) = match region {
+            Region::Ntsc | Region::Dendy => (&NTSC_STEP_CYCLES, NTSC_FIFTH_STEP_CYCLE),
+            Region::Pal => (&PAL_STEP_CYCLES, PAL_FIFTH_STEP_CYCLE),
+        };
+
+        FrameCounter {
+            step_cycles,
+            fifth_step_cycle,
+
+            five_step_mode: false,
+            irq_inhibit: false,
+            irq: false,
+
+            cycle: 0,
+            reset_delay: None,
+        }
+    }
+
+    /**
+     * Writes to bus::$4017
+     * The sequence restarts 3 CPU cycles after a write on an even cycle, or 4
+     * after a write on an odd cycle
+     */
+    pub fn write(&mut self, value: u8, odd_cycle: bool) {
+        self.five_step_mode = value & 0b1000_0000 != 0;
+        self.irq_inhibit = value & 0b0100_0000 != 0;
+        if self.irq_inhibit {
+            self.irq = false;
+        }
+        self.reset_delay = Some(if odd_cycle { 4 } else { 3 });
+    }
+
+    /**
+     * Advances the sequence by one CPU cycle.
+     * Returns the units to clock on this cycle.
+     */
+    pub fn clock(&mut self) -> FrameClocks {
+        if let Some(delay) = self.reset_delay {
+            if delay > 1 {
+                self.reset_delay = Some(delay - 1);
+            } else {
+                self.reset_delay = None;
+                self.cycle = 0;
+                // Restarting in 5-step mode clocks everything straight away
+                return if self.five_step_mode {
+                    FrameClocks::BOTH
+                } else {
+                    FrameClocks::NONE
+                };
+            }
+        }
+
+        self.cycle += 1;
+        let [first, second, third, fourth] = *self.step_cycles;
+        let cycle = self.cycle;
+
+        if cycle == first || cycle == third {
+            FrameClocks::QUARTER
+        } else if cycle == second {
+            FrameClocks::BOTH
+        } else if self.five_step_mode {
+            if cycle == self.fifth_step_cycle {
+                FrameClocks::BOTH
+            } else {
+                if cycle > self.fifth_step_cycle {
+                    self.cycle = 0;
+                }
+                FrameClocks::NONE
+            }
+        } else {
+            // The interrupt flag is set on the 3 cycles around the last step
+            if (fourth - 1..=fourth + 1).contains(&cycle) && !self.irq_inhibit {
+                self.irq = true;
+            }
+            if cycle > fourth {
+                self.cycle = 0;
+            }
+            if cycle == fourth {
+                FrameClocks::BOTH
+            } else {
+                FrameClocks::NONE
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        apu::frame_counter::{FrameClocks, FrameCounter},
+        region::Region,
+    };
+
+    /**
+     * Clocks the frame counter for the given number of cycles.
+     * Returns the cycles (1-based) each quarter and half frame clock happened on.
+     */
+    fn run(frame_counter: &mut FrameCounter, cycles: u32) -> (Vec<u32>, Vec<u32>) {
+        let mut quarter_frames = vec![];
+        let mut half_frames = vec![];
+        for cycle in 1..=cycles {
+            let clocks = frame_counter.clock();
+            if clocks.quarter_frame {
+                quarter_frames.push(cycle);
+            }
+            if clocks.half_frame {
+                half_frames.push(cycle);
+            }
+        }
+        (quarter_frames, half_frames)
+    }
+
+    #[test]
+    fn test_four_step_sequence() {
+        let mut frame_counter = FrameCounter::new(Region::Ntsc);
+        let (quarter_frames, half_frames) = run(&mut frame_counter, 29830 * 2);
+
+        assert_eq!(
+            quarter_frames,
+            vec![7457, 14913, 22371, 29829, 37287, 44743, 52201, 59659]
+        );
+        assert_eq!(half_frames, vec![14913, 29829, 44743, 59659]);
+    }
+
+    #[test]
+    fn test_four_step_irq() {
+        let mut frame_counter = FrameCounter::new(Region::Ntsc);
+        run(&mut frame_counter, 29827);
+        assert!(!frame_counter.irq);
+        frame_counter.clock();
+        assert!(frame_counter.irq);
+
+        // Set again on the next 2 cycles, even if it's acknowledged
+        frame_counter.irq = false;
+        frame_counter.clock();
+        assert!(frame_counter.irq);
+        frame_counter.irq = false;
+        frame_counter.clock();
+        assert!(frame_counter.irq);
+        frame_counter.irq = false;
+        frame_counter.clock();
+        assert!(!frame_counter.irq);
+    }
+
+    #[test]
+    fn test_irq_inhibit() {
+        let mut frame_counter = FrameCounter::new(Region::Ntsc);
+        run(&mut frame_counter, 29830);
+        assert!(frame_counter.irq);
+
+        frame_counter.write(0b0100_0000, false);
+        assert!(!frame_counter.irq);
+        run(&mut frame_counter, 29830 * 2);
+        assert!(!frame_counter.irq);
+    }
+
+    #[test]
+    fn test_five_step_sequence() {
+        let mut frame_counter = FrameCounter::new(Region::Ntsc);
+        frame_counter.write(0b1000_0000, false);
+        let (quarter_frames, half_frames) = run(&mut frame_counter, 3 + 37282);
+
+        // The write clocks both 3 cycles later, then restarts the sequence
+        assert_eq!(
+            quarter_frames,
+            vec![3, 3 + 7457, 3 + 14913, 3 + 22371, 3 + 37281]
+        );
+        assert_eq!(half_frames, vec![3, 3 + 14913, 3 + 37281]);
+        assert!(!frame_counter.irq);
+
+        let (quarter_frames, _) = run(&mut frame_counter, 7457);
+        assert_eq!(quarter_frames, vec![7457]);
+    }
+
+    #[test]
+    fn test_write_delay() {
+        let mut frame_counter = FrameCounter::new(Region::Ntsc);
+        frame_counter.write(0, true);
+        run(&mut frame_counter, 4);
+        assert_eq!(frame_counter.cycle, 0);
+        assert_eq!(frame_counter.clock(), FrameClocks::NONE);
+        assert_eq!(frame_counter.cycle, 1);
+    }
+
+    #[test]
+    fn test_pal_sequence() {
+        let mut frame_counter = FrameCounter::new(Region::Pal);
+        let (quarter_frames, half_frames) = run(&mut frame_counter, 33254);
+
+        assert_eq!(quarter_frames, vec![8313, 16627, 24939, 33253]);
+        assert_eq!(half_frames, vec![16627, 33253]);
+        assert!(frame_counter.irq);
+    }
+}
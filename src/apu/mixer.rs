@@ -0,0 +1,55 @@
+/**
+ * The channels' DACs aren't linear. The two pulse channels share one output,
+ * and the triangle, noise and DMC share another, each looked up by the sum of
+ * their levels. Both tables are in [0, 1).
+ */
+const PULSE_TABLE: [f32; 31] = pulse_table();
+const TND_TABLE: [f32; 203] = tnd_table();
+
+const fn pulse_table() -> [f32; 31] {
+    let mut table = [0.0; 31];
+    let mut level = 1;
+    while level < table.len() {
+        table[level] = 95.52 / (8128.0 / level as f32 + 100.0);
+        level += 1;
+    }
+    table
+}
+
+const fn tnd_table() -> [f32; 203] {
+    let mut table = [0.0; 203];
+    let mut level = 1;
+    while level < table.len() {
+        table[level] = 163.67 / (24329.0 / level as f32 + 100.0);
+        level += 1;
+    }
+    table
+}
+
+/**
+ * Mixes the channels' current levels into the APU's output amplitude, [0, 1)
+ */
+pub fn mix(pulse_1: u8, pulse_2: u8, triangle: u8, noise: u8, dmc: u8) -> f32 {
+    let pulse_level = pulse_1 as usize + pulse_2 as usize;
+    let tnd_level = 3 * triangle as usize + 2 * noise as usize + dmc as usize;
+    PULSE_TABLE[pulse_level] + TND_TABLE[tnd_level]
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::apu::mixer;
+
+    #[test]
+    fn test_mix() {
+        assert_eq!(mixer::mix(0, 0, 0, 0, 0), 0.0);
+
+        let pulses = mixer::mix(15, 15, 0, 0, 0);
+        assert!((pulses - 0.2575).abs() < 0.0001);
+        let tnd = mixer::mix(0, 0, 15, 15, 127);
+        assert!((tnd - 0.7425).abs() < 0.0001);
+        assert!(mixer::mix(15, 15, 15, 15, 127) < 1.0);
+
+        // Non-linear: two pulses at 15 are quieter than twice one at 15
+        assert!(pulses < 2.0 * mixer::mix(15, 0, 0, 0, 0));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{
+    apu::{Envelope, LengthCounter},
+    region::Region,
+};
+
+/**
+ * Timer periods in CPU cycles, indexed by the low 4 bits of $400E
+ */
+const NTSC_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+/**
+ * The noise channel. Outputs pseudo-random bits from a 15-bit linear feedback
+ * shift register. Registers:
+ *
+ * $400C: --LC VVVV - Length counter halt, envelope (see Envelope)
+ * $400D: Unused
+ * $400E: M--- PPPP - Short mode, timer period index
+ * $400F: LLLL L--- - Length counter load
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Noise {
+    period_table: &'static [u16; 16],
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+
+    /**
+     * Takes feedback from bit 6 instead of bit 1, which repeats the sequence
+     * after 93 or 31 bits instead of 32767, for a metallic tone
+     */
+    short_mode: bool,
+    shift_register: u16,
+    timer_period: u16,
+    timer: u16,
+}
+
+impl Noise {
+    pub fn new(region: Region) -> Self {
+        let period_table = match region {
+            Region::Ntsc | Region::Dendy => &NTSC_PERIOD_TABLE,
+            Region::Pal => &PAL_PERIOD_TABLE,
+        };
+
+        Noise {
+            period_table,
+            envelope: Envelope::default(),
+            length_counter: LengthCounter::default(),
+
+            short_mode: false,
+            shift_register: 1,
+            timer_period: period_table[0],
+            timer: 0,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address & 0b11 {
+            0 => {
+                self.length_counter.set_halted(value & 0b0010_0000 != 0);
+                self.envelope.write(value);
+            }
+            1 => {}
+            2 => {
+                self.short_mode = value & 0b1000_0000 != 0;
+                self.timer_period = self.period_table[(value & 0b1111) as usize];
+            }
+            _ => {
+                self.length_counter.load(value >> 3);
+                self.envelope.restart();
+            }
+        }
+    }
+
+    /**
+     * Clocked every CPU cycle. Shifts the register once per timer period.
+     */
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            let feedback_bit = if self.short_mode { 6 } else { 1 };
+            let feedback = (self.shift_register ^ (self.shift_register >> feedback_bit)) & 1;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /**
+     * Current volume, [0, 15]
+     */
+    pub fn output(&self) -> u8 {
+        if self.shift_register & 1 != 0 || !self.length_counter.is_active() {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{apu::noise::Noise, region::Region};
+
+    /**
+     * Clocks the noise channel's shift register until it repeats.
+     * Returns the length of the sequence.
+     */
+    fn sequence_length(noise: &mut Noise) -> usize {
+        let start = noise.shift_register;
+        let mut length = 0;
+        loop {
+            for _ in 0..noise.timer_period {
+                noise.clock_timer();
+            }
+            length += 1;
+            if noise.shift_register == start {
+                return length;
+            }
+        }
+    }
+
+    #[test]
+    fn test_noise_sequence_length() {
+        let mut noise = Noise::new(Region::Ntsc);
+        assert_eq!(sequence_length(&mut noise), 32767);
+
+        noise.write_register(0x400E, 0b1000_0000);
+        assert_eq!(sequence_length(&mut noise), 93);
+    }
+
+    #[test]
+    fn test_noise_period() {
+        let mut noise = Noise::new(Region::Ntsc);
+        noise.write_register(0x400E, 0b0000_0010);
+        noise.clock_timer();
+        let shift_register = noise.shift_register;
+        for _ in 0..15 {
+            noise.clock_timer();
+        }
+        assert_eq!(noise.shift_register, shift_register);
+        noise.clock_timer();
+        assert_ne!(noise.shift_register, shift_register);
+
+        let noise = Noise::new(Region::Pal);
+        assert_eq!(noise.period_table[2], 14);
+    }
+
+    #[test]
+    fn test_noise_output() {
+        let mut noise = Noise::new(Region::Ntsc);
+        noise.length_counter.set_enabled(true);
+        noise.write_register(0x400C, 0b0001_1001);
+        noise.write_register(0x400F, 0b0000_1000);
+
+        // Bit 0 of the shift register mutes the channel
+        assert_eq!(noise.output(), 0);
+        noise.shift_register = 0b10;
+        assert_eq!(noise.output(), 9);
+    }
+}
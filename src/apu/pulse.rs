@@ -0,0 +1,260 @@
+use crate::apu::{Envelope, LengthCounter};
+
+const MAX_TIMER_PERIOD: u16 = 0x7FF;
+// Periods below 8 would be ultrasonic, so they're muted
+const MIN_TIMER_PERIOD: u16 = 8;
+
+/**
+ * Waveforms for each duty cycle: 12.5%, 25%, 50% and 25% negated
+ */
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/**
+ * The two pulse channels differ only in how their sweeps negate
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PulseChannel {
+    /**
+     * Negates with one's complement: period - change - 1
+     */
+    One,
+    /**
+     * Negates with two's complement: period - change
+     */
+    Two,
+}
+
+/**
+ * A square wave channel. Registers, offset from $4000 (pulse 1) or $4004 (pulse 2):
+ *
+ * 0: DDLC VVVV - Duty, length counter halt, envelope (see Envelope)
+ * 1: EPPP NSSS - Sweep enabled, period, negate, shift count
+ * 2: TTTT TTTT - Timer period, low byte
+ * 3: LLLL LTTT - Length counter load, timer period high 3 bits
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Pulse {
+    channel: PulseChannel,
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+
+    duty: u8,
+    sequence_step: u8,
+    timer_period: u16,
+    timer: u16,
+
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_divider: u8,
+    sweep_reload: bool,
+}
+
+impl Pulse {
+    pub fn new(channel: PulseChannel) -> Self {
+        Pulse {
+            channel,
+            envelope: Envelope::default(),
+            length_counter: LengthCounter::default(),
+
+            duty: 0,
+            sequence_step: 0,
+            timer_period: 0,
+            timer: 0,
+
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_divider: 0,
+            sweep_reload: false,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address & 0b11 {
+            0 => {
+                self.duty = value >> 6;
+                self.length_counter.set_halted(value & 0b0010_0000 != 0);
+                self.envelope.write(value);
+            }
+            1 => {
+                self.sweep_enabled = value & 0b1000_0000 != 0;
+                self.sweep_period = (value >> 4) & 0b111;
+                self.sweep_negate = value & 0b0000_1000 != 0;
+                self.sweep_shift = value & 0b111;
+                self.sweep_reload = true;
+            }
+            2 => self.timer_period = (self.timer_period & 0x0700) | value as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((value & 0b111) as u16) << 8;
+                self.length_counter.load(value >> 3);
+                self.sequence_step = 0;
+                self.envelope.restart();
+            }
+        }
+    }
+
+    /**
+     * Clocked every other CPU cycle. Steps through the duty cycle once every
+     * timer period + 1 clocks.
+     */
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_step = (self.sequence_step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /**
+     * Clocked every half frame. Moves the period towards the sweep's target.
+     */
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.is_muted()
+        {
+            self.timer_period = self.target_period();
+        }
+
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    /**
+     * The period the sweep is moving towards. It's calculated constantly, even
+     * when the sweep is disabled.
+     */
+    fn target_period(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if !self.sweep_negate {
+            self.timer_period + change
+        } else {
+            match self.channel {
+                PulseChannel::One => self.timer_period.saturating_sub(change + 1),
+                PulseChannel::Two => self.timer_period.saturating_sub(change),
+            }
+        }
+    }
+
+    /**
+     * The sweep mutes the channel when the period is too low, or its target
+     * is out of range
+     */
+    fn is_muted(&self) -> bool {
+        self.timer_period < MIN_TIMER_PERIOD || self.target_period() > MAX_TIMER_PERIOD
+    }
+
+    /**
+     * Current volume, [0, 15]
+     */
+    pub fn output(&self) -> u8 {
+        let silent = DUTY_TABLE[self.duty as usize][self.sequence_step as usize] == 0
+            || !self.length_counter.is_active()
+            || self.is_muted();
+        if silent {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::apu::pulse::{Pulse, PulseChannel};
+
+    /**
+     * An enabled pulse channel at constant volume 10, with the given timer period
+     */
+    fn new_playing_pulse(channel: PulseChannel, timer_period: u16) -> Pulse {
+        let mut pulse = Pulse::new(channel);
+        pulse.length_counter.set_enabled(true);
+        pulse.write_register(0x4000, 0b1001_1010);
+        pulse.write_register(0x4002, timer_period as u8);
+        pulse.write_register(0x4003, (timer_period >> 8) as u8);
+        pulse
+    }
+
+    #[test]
+    fn test_pulse_duty_cycle() {
+        let mut pulse = new_playing_pulse(PulseChannel::One, 8);
+        let mut waveform = vec![];
+        for _ in 0..8 {
+            waveform.push(pulse.output());
+            for _ in 0..9 {
+                pulse.clock_timer();
+            }
+        }
+        // 50% duty
+        assert_eq!(waveform, vec![0, 10, 10, 10, 10, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_pulse_sweep_negate() {
+        // Negate, shift 1
+        let mut pulse_1 = new_playing_pulse(PulseChannel::One, 0x100);
+        let mut pulse_2 = new_playing_pulse(PulseChannel::Two, 0x100);
+        pulse_1.write_register(0x4001, 0b1000_1001);
+        pulse_2.write_register(0x4005, 0b1000_1001);
+
+        pulse_1.clock_sweep();
+        pulse_2.clock_sweep();
+        assert_eq!(pulse_1.timer_period, 0x7F);
+        assert_eq!(pulse_2.timer_period, 0x80);
+    }
+
+    #[test]
+    fn test_pulse_sweep_period() {
+        // Period 1, shift 2
+        let mut pulse = new_playing_pulse(PulseChannel::One, 0x100);
+        pulse.write_register(0x4001, 0b1001_0010);
+
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x140);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x140);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x190);
+    }
+
+    #[test]
+    fn test_pulse_sweep_muting() {
+        let mut pulse = new_playing_pulse(PulseChannel::One, 0x400);
+        pulse.sequence_step = 1;
+        // With shift 0 the target is double the period, which is out of range
+        // even though the sweep is disabled
+        assert_eq!(pulse.output(), 0);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0x400);
+
+        // Negate
+        pulse.write_register(0x4001, 0b0000_1000);
+        assert_eq!(pulse.output(), 10);
+
+        let mut pulse = new_playing_pulse(PulseChannel::One, 7);
+        pulse.sequence_step = 1;
+        assert_eq!(pulse.output(), 0);
+    }
+
+    #[test]
+    fn test_pulse_length_register_restarts() {
+        let mut pulse = new_playing_pulse(PulseChannel::Two, 0x100);
+        pulse.sequence_step = 5;
+        pulse.write_register(0x4007, 0b0000_1001);
+
+        assert_eq!(pulse.sequence_step, 0);
+        assert_eq!(pulse.timer_period, 0x100);
+        assert!(pulse.length_counter.is_active());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::apu::LengthCounter;
+
+/**
+ * Output level at each step of the triangle wave
+ */
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+/**
+ * The triangle wave channel. It has no volume control, but a linear counter
+ * that silences it like the length counter, with quarter frame precision.
+ * Registers:
+ *
+ * $4008: CRRR RRRR - Control (linear counter reload, and length counter halt),
+ *                    linear counter reload value
+ * $4009: Unused
+ * $400A: TTTT TTTT - Timer period, low byte
+ * $400B: LLLL LTTT - Length counter load, timer period high 3 bits
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Triangle {
+    pub length_counter: LengthCounter,
+
+    control: bool,
+    linear_counter_period: u8,
+    linear_counter: u8,
+    linear_counter_reload: bool,
+
+    sequence_step: u8,
+    timer_period: u16,
+    timer: u16,
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Triangle {
+            length_counter: LengthCounter::default(),
+
+            control: false,
+            linear_counter_period: 0,
+            linear_counter: 0,
+            linear_counter_reload: false,
+
+            sequence_step: 0,
+            timer_period: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address & 0b11 {
+            0 => {
+                self.control = value & 0b1000_0000 != 0;
+                self.length_counter.set_halted(self.control);
+                self.linear_counter_period = value & 0b0111_1111;
+            }
+            1 => {}
+            2 => self.timer_period = (self.timer_period & 0x0700) | value as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((value & 0b111) as u16) << 8;
+                self.length_counter.load(value >> 3);
+                self.linear_counter_reload = true;
+            }
+        }
+    }
+
+    /**
+     * Clocked every CPU cycle. The wave only moves while both counters are
+     * non-zero. Otherwise it holds its level, rather than dropping to 0.
+     */
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.linear_counter > 0 && self.length_counter.is_active() {
+                self.sequence_step = (self.sequence_step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /**
+     * Clocked every quarter frame
+     */
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_counter_reload {
+            self.linear_counter = self.linear_counter_period;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+
+        if !self.control {
+            self.linear_counter_reload = false;
+        }
+    }
+
+    /**
+     * Current level, [0, 15]
+     */
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.sequence_step as usize]
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::apu::triangle::Triangle;
+
+    /**
+     * An enabled triangle channel with timer period 0, and the given linear
+     * counter register
+     */
+    fn new_playing_triangle(linear_counter: u8) -> Triangle {
+        let mut triangle = Triangle::new();
+        triangle.length_counter.set_enabled(true);
+        triangle.write_register(0x4008, linear_counter);
+        triangle.write_register(0x400A, 0);
+        triangle.write_register(0x400B, 0b0000_1000);
+        triangle
+    }
+
+    #[test]
+    fn test_triangle_sequence() {
+        let mut triangle = new_playing_triangle(10);
+        triangle.clock_linear_counter();
+
+        let mut waveform = vec![triangle.output()];
+        for _ in 0..32 {
+            triangle.clock_timer();
+            waveform.push(triangle.output());
+        }
+        assert_eq!(waveform[..4], [15, 14, 13, 12]);
+        assert_eq!(waveform[15..19], [0, 0, 1, 2]);
+        assert_eq!(waveform[31..], [15, 15]);
+    }
+
+    #[test]
+    fn test_triangle_linear_counter() {
+        let mut triangle = new_playing_triangle(2);
+        // The counter is only reloaded on the next quarter frame
+        triangle.clock_timer();
+        assert_eq!(triangle.output(), 15);
+
+        triangle.clock_linear_counter();
+        triangle.clock_linear_counter();
+        triangle.clock_timer();
+        assert_eq!(triangle.output(), 14);
+
+        // Holds its level once the counter runs out
+        triangle.clock_linear_counter();
+        triangle.clock_timer();
+        assert_eq!(triangle.output(), 14);
+    }
+
+    #[test]
+    fn test_triangle_control_keeps_reloading() {
+        let mut triangle = new_playing_triangle(0b1000_0001);
+        for _ in 0..4 {
+            triangle.clock_linear_counter();
+        }
+        triangle.clock_timer();
+        assert_eq!(triangle.output(), 14);
+    }
+}
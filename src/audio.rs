@@ -0,0 +1,125 @@
+mod filter;
+mod resampler;
+
+use self::{
+    filter::{Filter, FilterKind},
+    resampler::Resampler,
+};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/**
+ * Samples are resampled in batches of at least this many
+ */
+const BATCH_SAMPLES: usize = 64;
+
+/**
+ * Turns the APU's output, one amplitude per CPU cycle, into mono samples at
+ * the output sample rate. Like the NES's audio output, it's filtered by two
+ * high-pass filters, at 90Hz and 440Hz, and a low-pass filter at 14kHz.
+ * Samples are in [-1, 1].
+ */
+#[derive(Debug, PartialEq)]
+pub struct Audio {
+    clock_rate: u32,
+    sample_rate: u32,
+    resampler: Resampler,
+    filters: [Filter; 3],
+    samples: Vec<f32>,
+}
+
+impl Audio {
+    /**
+     * clock_rate is the number of amplitudes pushed per second
+     */
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Audio {
+            clock_rate,
+            sample_rate,
+            resampler: Resampler::new(clock_rate, sample_rate),
+            filters: [
+                Filter::new(FilterKind::HighPass, 90.0, sample_rate),
+                Filter::new(FilterKind::HighPass, 440.0, sample_rate),
+                Filter::new(FilterKind::LowPass, 14000.0, sample_rate),
+            ],
+            samples: vec![],
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /**
+     * Restarts the output at a new sample rate, dropping samples that haven't
+     * been taken yet
+     */
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        *self = Audio::new(self.clock_rate, sample_rate);
+    }
+
+    /**
+     * Pushes the amplitude for one CPU cycle
+     */
+    pub fn push(&mut self, amplitude: f32) {
+        self.resampler.clock(amplitude);
+        if self.resampler.available() >= BATCH_SAMPLES {
+            self.resample();
+        }
+    }
+
+    /**
+     * Returns the samples output since the last call. Samples older than a
+     * second are dropped, so output that's never taken doesn't pile up.
+     */
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        self.resample();
+        std::mem::take(&mut self.samples)
+    }
+
+    fn resample(&mut self) {
+        let start = self.samples.len();
+        self.resampler.read_samples(&mut self.samples);
+        for sample in &mut self.samples[start..] {
+            *sample = self
+                .filters
+                .iter_mut()
+                .fold(*sample, |sample, filter| filter.process(sample));
+        }
+
+        let max_samples = self.sample_rate as usize;
+        if self.samples.len() > max_samples {
+            self.samples.drain(..self.samples.len() - max_samples);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::audio::Audio;
+
+    #[test]
+    fn test_audio() {
+        let mut audio = Audio::new(1_789_773, 48000);
+        for cycle in 0..1_789_773 / 60 {
+            // About 440Hz
+            audio.push(if cycle & 0x800 == 0 { 0.5 } else { 0.0 });
+        }
+        let samples = audio.take_samples();
+        assert!((799..=800).contains(&samples.len()));
+        // The high-pass filters center it on 0
+        assert!(samples.iter().any(|&sample| sample > 0.1));
+        assert!(samples.iter().any(|&sample| sample < -0.1));
+        assert!(samples.iter().all(|sample| sample.abs() <= 1.0));
+        assert!(audio.take_samples().is_empty());
+    }
+
+    #[test]
+    fn test_audio_drops_old_samples() {
+        let mut audio = Audio::new(1_789_773, 44100);
+        for _ in 0..1_789_773 * 2 {
+            audio.push(0.0);
+        }
+        assert_eq!(audio.take_samples().len(), 44100);
+    }
+}
@@ -0,0 +1,81 @@
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FilterKind {
+    HighPass,
+    LowPass,
+}
+
+/**
+ * A first-order RC filter, run at the output sample rate
+ */
+#[derive(Debug, PartialEq)]
+pub struct Filter {
+    kind: FilterKind,
+    alpha: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl Filter {
+    /**
+     * Filters out frequencies below/above the cutoff, in Hz
+     */
+    pub fn new(kind: FilterKind, cutoff: f32, sample_rate: u32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate as f32;
+        let alpha = match kind {
+            FilterKind::HighPass => rc / (rc + dt),
+            FilterKind::LowPass => dt / (rc + dt),
+        };
+
+        Filter {
+            kind,
+            alpha,
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = match self.kind {
+            FilterKind::HighPass => {
+                self.alpha * (self.previous_output + input - self.previous_input)
+            }
+            FilterKind::LowPass => {
+                self.previous_output + self.alpha * (input - self.previous_output)
+            }
+        };
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::audio::filter::{Filter, FilterKind};
+
+    #[test]
+    fn test_high_pass_removes_dc() {
+        let mut filter = Filter::new(FilterKind::HighPass, 90.0, 44100);
+        assert!(filter.process(1.0) > 0.98);
+        let output = (0..44100).map(|_| filter.process(1.0)).last().unwrap();
+        assert!(output.abs() < 0.001);
+    }
+
+    #[test]
+    fn test_low_pass() {
+        let mut filter = Filter::new(FilterKind::LowPass, 14000.0, 44100);
+        let output = (0..100).map(|_| filter.process(1.0)).last().unwrap();
+        assert!((output - 1.0).abs() < 0.001);
+
+        // Alternating at the Nyquist frequency is mostly filtered out
+        let mut filter = Filter::new(FilterKind::LowPass, 14000.0, 44100);
+        let peak = (0..100)
+            .map(|i| filter.process(if i & 1 == 0 { 1.0 } else { -1.0 }))
+            .skip(50)
+            .fold(0.0f32, |peak, output| peak.max(output.abs()));
+        assert!(peak < 0.5);
+    }
+}
@@ -0,0 +1,176 @@
+use std::f64::consts::PI;
+
+/**
+ * Output samples each band-limited step is spread over
+ */
+const KERNEL_WIDTH: usize = 16;
+/**
+ * Steps are placed with 1/32 sample precision
+ */
+const KERNEL_PHASES: usize = 32;
+/**
+ * Cutoff frequency, as a fraction of the output sample rate. Just under the
+ * Nyquist frequency, so the kernel's roll-off doesn't alias.
+ */
+const CUTOFF: f64 = 0.45;
+
+/**
+ * Windowed sinc impulses, one per phase, each summing to 1
+ */
+fn build_kernel() -> Vec<[f32; KERNEL_WIDTH]> {
+    let half_width = (KERNEL_WIDTH / 2) as f64;
+    (0..KERNEL_PHASES)
+        .map(|phase| {
+            let offset = phase as f64 / KERNEL_PHASES as f64;
+            let impulse: Vec<f64> = (0..KERNEL_WIDTH)
+                .map(|tap| {
+                    let x = tap as f64 - half_width - offset;
+                    let y = PI * 2.0 * CUTOFF * x;
+                    let sinc = if y == 0.0 { 1.0 } else { y.sin() / y };
+                    // Blackman window, reaching 0 a sample beyond the first tap
+                    let window = 0.42
+                        + 0.5 * (PI * x / (half_width + 1.0)).cos()
+                        + 0.08 * (2.0 * PI * x / (half_width + 1.0)).cos();
+                    sinc * window
+                })
+                .collect();
+
+            let sum: f64 = impulse.iter().sum();
+            let mut taps = [0.0; KERNEL_WIDTH];
+            for (tap, value) in taps.iter_mut().zip(impulse) {
+                *tap = (value / sum) as f32;
+            }
+            taps
+        })
+        .collect()
+}
+
+/**
+ * Resamples a signal given every CPU cycle down to the output sample rate.
+ * The APU's output is a series of steps, so rather than filtering every
+ * cycle, each change in amplitude adds a band-limited step to the output.
+ * Steps are kept as deltas, which are summed when samples are read.
+ * Output lags the input by KERNEL_WIDTH / 2 samples.
+ */
+#[derive(Debug, PartialEq)]
+pub struct Resampler {
+    kernel: Vec<[f32; KERNEL_WIDTH]>,
+    /**
+     * Output samples per input cycle
+     */
+    step: f64,
+    /**
+     * Position of the current cycle, in output samples after deltas[0]
+     */
+    time: f64,
+    amplitude: f32,
+    deltas: Vec<f32>,
+    accumulator: f32,
+}
+
+impl Resampler {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Resampler {
+            kernel: build_kernel(),
+            step: sample_rate as f64 / clock_rate as f64,
+            time: 0.0,
+            amplitude: 0.0,
+            deltas: vec![],
+            accumulator: 0.0,
+        }
+    }
+
+    /**
+     * Advances by one input cycle, with the signal at the given amplitude
+     */
+    pub fn clock(&mut self, amplitude: f32) {
+        if amplitude != self.amplitude {
+            self.add_step(amplitude - self.amplitude);
+            self.amplitude = amplitude;
+        }
+        self.time += self.step;
+    }
+
+    fn add_step(&mut self, delta: f32) {
+        let index = self.time as usize;
+        let phase = (self.time.fract() * KERNEL_PHASES as f64) as usize;
+        if self.deltas.len() < index + KERNEL_WIDTH {
+            self.deltas.resize(index + KERNEL_WIDTH, 0.0);
+        }
+
+        for (sample, tap) in self.deltas[index..].iter_mut().zip(self.kernel[phase]) {
+            *sample += delta * tap;
+        }
+    }
+
+    /**
+     * Number of samples that no later step can change
+     */
+    pub fn available(&self) -> usize {
+        self.time as usize
+    }
+
+    /**
+     * Moves the available samples to the end of output
+     */
+    pub fn read_samples(&mut self, output: &mut Vec<f32>) {
+        let count = self.available();
+        for index in 0..count {
+            self.accumulator += self.deltas.get(index).copied().unwrap_or(0.0);
+            output.push(self.accumulator);
+        }
+
+        self.deltas.drain(..count.min(self.deltas.len()));
+        self.time -= count as f64;
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::audio::resampler::{Resampler, KERNEL_WIDTH};
+
+    #[test]
+    fn test_resample_rate() {
+        let mut resampler = Resampler::new(1_789_773, 44100);
+        let mut samples = vec![];
+        for _ in 0..1_789_773 {
+            resampler.clock(0.0);
+            resampler.read_samples(&mut samples);
+        }
+        assert!((44099..=44100).contains(&samples.len()));
+    }
+
+    #[test]
+    fn test_resample_step() {
+        let mut resampler = Resampler::new(1_789_773, 44100);
+        let mut samples = vec![];
+        for _ in 0..2000 {
+            resampler.clock(0.5);
+        }
+        resampler.read_samples(&mut samples);
+
+        // Rises half the kernel's width in, and settles at the amplitude
+        assert!(samples[0].abs() < 0.01);
+        assert!(samples[KERNEL_WIDTH / 2 - 1] < 0.25);
+        assert!(samples[KERNEL_WIDTH / 2] > 0.25);
+        assert!(samples[KERNEL_WIDTH..]
+            .iter()
+            .all(|sample| (sample - 0.5).abs() < 0.01));
+    }
+
+    #[test]
+    fn test_resample_band_limited() {
+        // A square wave far above the output's Nyquist frequency comes out as
+        // its average, instead of aliasing
+        let mut resampler = Resampler::new(1_789_773, 44100);
+        let mut samples = vec![];
+        for cycle in 0..20000 {
+            resampler.clock(if cycle & 0b10 == 0 { 1.0 } else { 0.0 });
+        }
+        resampler.read_samples(&mut samples);
+
+        assert!(samples[KERNEL_WIDTH..]
+            .iter()
+            .all(|sample| (sample - 0.5).abs() < 0.05));
+    }
+}
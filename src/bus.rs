@@ -1,5 +1,7 @@
 use crate::{
-    config::{CPU_PAGE_SIZE, PROGRAM_ROM_PAGE_SIZE},
+    apu::{CHANNEL_REGISTERS_END, CHANNEL_REGISTERS_START},
+    cartridge::{CPU_CARTRIDGE_END, CPU_CARTRIDGE_START},
+    config::CPU_PAGE_SIZE,
     console::Console,
 };
 
@@ -7,8 +9,9 @@ const RAM_START: u16 = 0x0000;
 const RAM_MIRRORS_END: u16 = 0x1FFF;
 const PPU_REGISTERS_START: u16 = 0x2000;
 const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
-const ROM_START: u16 = 0xC000;
-const ROM_END: u16 = 0xFFFF;
+const OAM_DMA: u16 = 0x4014;
+const APU_STATUS: u16 = 0x4015;
+const APU_FRAME_COUNTER: u16 = 0x4017;
 
 const CPU_RAM_MIRROR_DOWN_MASK: u16 = 0b0000_0111_1111_1111;
 const PPU_MIRROR_DOWN_MASK: u16 = 0b0010_0000_0000_0111;
@@ -34,12 +37,10 @@ pub fn read_u8(console: &mut Console, address: u16) -> u8 {
             let mirrored_down = address & PPU_MIRROR_DOWN_MASK;
 
             match mirrored_down {
-                    0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 | 0x4014 => {
-                        panic!("Attempt to read from write-only PPU address: {:04X}, mirrored down to : {:04X}", address, mirrored_down)
-                    }
+                    0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 => console.ppu.read_from_io_latch(),
                     0x2002 => console.ppu.read_from_status(),
                     0x2004 => console.ppu.read_from_oam_data(),
-                    0x2007 => console.ppu.read_from_data(),
+                    0x2007 => console.ppu.read_from_data(console.cartridge.as_mut()),
                     _ => panic!(
                         "Attempt to read from invalid address in PPU range: {:04X}, mirrored-down to: {:04X}",
                         address,
@@ -47,20 +48,8 @@ pub fn read_u8(console: &mut Console, address: u16) -> u8 {
                     ),
                 }
         }
-        ROM_START..=ROM_END => {
-            let rom_address = address - ROM_START;
-            let single_page_program_rom =
-                console.rom.program_rom.len() as u16 == PROGRAM_ROM_PAGE_SIZE;
-
-            let first_mirror_rom_address =
-                if single_page_program_rom && rom_address >= PROGRAM_ROM_PAGE_SIZE {
-                    rom_address % PROGRAM_ROM_PAGE_SIZE
-                } else {
-                    rom_address
-                };
-
-            console.rom.program_rom[first_mirror_rom_address as usize]
-        }
+        APU_STATUS => console.apu.read_status(),
+        CPU_CARTRIDGE_START..=CPU_CARTRIDGE_END => console.cartridge.cpu_read(address),
         _ => {
             panic!("Invalid attempt to read at {:X}", address)
         }
@@ -85,6 +74,32 @@ pub fn read_u16_wrap_page(console: &mut Console, address: u16) -> u16 {
     u16::from_le_bytes([low_byte, high_byte])
 }
 
+/**
+ * Reads without side effects, for debugging. Registers read as 0, since reading
+ * them can acknowledge interrupts, race vblank or advance the VRAM address.
+ */
+pub fn peek_u8(console: &mut Console, address: u16) -> u8 {
+    match address {
+        RAM_START..=RAM_MIRRORS_END => {
+            let mirrored_down = address & CPU_RAM_MIRROR_DOWN_MASK;
+            console.bus.cpu_ram[mirrored_down as usize]
+        }
+        CPU_CARTRIDGE_START..=CPU_CARTRIDGE_END => console.cartridge.cpu_read(address),
+        _ => 0,
+    }
+}
+
+/**
+ * Like read_u16_wrap_page, without side effects
+ */
+pub fn peek_u16_wrap_page(console: &mut Console, address: u16) -> u16 {
+    let low_byte = peek_u8(console, address);
+    let page_start = (address / CPU_PAGE_SIZE) * CPU_PAGE_SIZE;
+    let high_byte_address = page_start + ((address + 1) % CPU_PAGE_SIZE);
+    let high_byte = peek_u8(console, high_byte_address);
+    u16::from_le_bytes([low_byte, high_byte])
+}
+
 pub fn write_u8(console: &mut Console, address: u16, value: u8) {
     match address {
         RAM_START..=RAM_MIRRORS_END => {
@@ -93,29 +108,34 @@ pub fn write_u8(console: &mut Console, address: u16, value: u8) {
         }
         PPU_REGISTERS_START..=PPU_REGISTERS_MIRRORS_END => {
             let mirrored_down = address & PPU_MIRROR_DOWN_MASK;
+            console.ppu.write_to_io_latch(value);
             match mirrored_down {
-                    0x2002 => panic!("Attempt to write to read-only ppu address: {:40X}, mirrored-down to: {:40X}", address, mirrored_down),
+                    // Read-only, so the write only reaches the I/O latch
+                    0x2002 => {}
                     0x2000 => console.ppu.write_to_control(value),
                     0x2001 => console.ppu.write_to_mask(value),
                     0x2003 => console.ppu.write_to_oam_address(value),
                     0x2004 => console.ppu.write_to_oam_data(value),
                     0x2005 => console.ppu.write_to_scroll(value),
                     0x2006 => console.ppu.write_to_vram_address(value),
-                    0x2007 => console.ppu.write_to_data(value),
-                    0x4014 => {
-                        let mut data: [u8; 256] = [0; 256];
-                        let page_start = (value as u16) << 8;
-                        for byte in 0..256 {
-                            data[byte as usize] = read_u8(console, page_start + byte)
-                        }
-                        console.ppu.write_to_oam_dma(&data);
-                    },
+                    0x2007 => console.ppu.write_to_data(console.cartridge.as_mut(), value),
                     _ => panic!("Attempt to write to invalid address in ppu range: {:40X}, mirrored-down to: {:40X}", address, mirrored_down)
                 }
         }
-        ROM_START..=ROM_END => {
-            panic!("Invalid attempt to write to ROM at {:X}", address)
+        CHANNEL_REGISTERS_START..=CHANNEL_REGISTERS_END => {
+            console.apu.write_register(address, value)
+        }
+        OAM_DMA => {
+            let mut data: [u8; 256] = [0; 256];
+            let page_start = (value as u16) << 8;
+            for byte in 0..256 {
+                data[byte as usize] = read_u8(console, page_start + byte)
+            }
+            console.ppu.write_to_oam_dma(&data);
         }
+        APU_STATUS => console.apu.write_status(value),
+        APU_FRAME_COUNTER => console.apu.write_frame_counter(value),
+        CPU_CARTRIDGE_START..=CPU_CARTRIDGE_END => console.cartridge.cpu_write(address, value),
         _ => {
             panic!("Invalid attempt to write at {:X}", address)
         }
@@ -0,0 +1,226 @@
+mod axrom;
+mod cnrom;
+mod gxrom;
+mod mmc1;
+mod mmc3;
+mod nrom;
+mod uxrom;
+
+use std::fmt::Debug;
+
+use crate::{
+    config::CHR_ROM_PAGE_SIZE,
+    rom::{Mapper, Mirroring, Rom},
+};
+
+use self::{
+    axrom::Axrom, cnrom::Cnrom, gxrom::Gxrom, mmc1::Mmc1, mmc3::Mmc3, nrom::Nrom, uxrom::Uxrom,
+};
+
+pub const CPU_CARTRIDGE_START: u16 = 0x4020;
+pub const CPU_CARTRIDGE_END: u16 = 0xFFFF;
+pub const PPU_CARTRIDGE_START: u16 = 0x0000;
+pub const PPU_CARTRIDGE_END: u16 = 0x1FFF;
+
+const PROGRAM_RAM_SIZE: usize = 0x2000;
+/**
+ * Read from CPU addresses the board doesn't map. Hardware reads whatever was
+ * last on the data bus, which isn't tracked.
+ */
+const OPEN_BUS: u8 = 0;
+/**
+ * Offset of $7000 into PRG-RAM
+ */
+const TRAINER_OFFSET: usize = 0x1000;
+
+/**
+ * A game cartridge. Owns the PRG and CHR memory, and maps the cartridge
+ * parts of the CPU and PPU address spaces onto it.
+ */
+pub trait Cartridge: Debug {
+    /**
+     * Reads from CPU address space, [$4020, $FFFF]
+     */
+    fn cpu_read(&mut self, address: u16) -> u8;
+
+    /**
+     * Writes to CPU address space, [$4020, $FFFF]
+     */
+    fn cpu_write(&mut self, address: u16, value: u8);
+
+    /**
+     * Reads from PPU address space, [$0000, $1FFF]
+     */
+    fn ppu_read(&mut self, address: u16) -> u8;
+
+    /**
+     * Writes to PPU address space, [$0000, $1FFF]
+     */
+    fn ppu_write(&mut self, address: u16, value: u8);
+
+    /**
+     * The current nametable mirroring. May be changed at runtime by the mapper.
+     */
+    fn mirroring(&self) -> Mirroring;
+
+    /**
+     * Called once per PPU dot, so mappers can time the PPU's bus accesses
+     */
+    fn tick_ppu_dot(&mut self) {}
+
+    /**
+     * Whether the cartridge is asserting the CPU's IRQ line
+     */
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /**
+     * The cartridge's battery-backed PRG-RAM, if it has any.
+     * Its contents should be kept between runs.
+     */
+    fn battery_ram(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+}
+
+/**
+ * Pattern table memory. CHR-ROM if the ROM has any, otherwise writable CHR-RAM.
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct ChrMemory {
+    data: Vec<u8>,
+    writable: bool,
+}
+
+impl ChrMemory {
+    /**
+     * Uses the given CHR-ROM, or allocates chr_ram_size bytes of CHR-RAM (at
+     * least 8KB) if it's empty
+     */
+    pub fn new(chr_rom: Vec<u8>, chr_ram_size: usize) -> Self {
+        if chr_rom.is_empty() {
+            ChrMemory {
+                data: vec![0; chr_ram_size.max(CHR_ROM_PAGE_SIZE as usize)],
+                writable: true,
+            }
+        } else {
+            ChrMemory {
+                data: chr_rom,
+                writable: false,
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn read(&self, index: usize) -> u8 {
+        self.data[index % self.data.len()]
+    }
+
+    /**
+     * Writes to CHR-RAM. Writes to CHR-ROM are ignored.
+     */
+    pub fn write(&mut self, index: usize, value: u8) {
+        if self.writable {
+            let length = self.data.len();
+            self.data[index % length] = value;
+        }
+    }
+}
+
+/**
+ * PRG-RAM for [$6000, $7FFF], sized from the ROM header: battery-backed
+ * PRG-NVRAM, followed by volatile PRG-RAM. RAM smaller than 8KB is mirrored
+ * through the window. Without any, reads are open bus.
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct ProgramRam {
+    data: Vec<u8>,
+    battery_size: usize,
+}
+
+impl ProgramRam {
+    /**
+     * Loads the ROM's trainer (if any) at $7000, making the RAM 8KB if it's
+     * smaller
+     */
+    pub fn new(rom: &Rom) -> Self {
+        let mut data = vec![0; rom.program_nvram_size + rom.program_ram_size];
+        if let Some(trainer) = &rom.trainer {
+            if data.len() < PROGRAM_RAM_SIZE {
+                data.resize(PROGRAM_RAM_SIZE, 0);
+            }
+            data[TRAINER_OFFSET..(TRAINER_OFFSET + trainer.len())].copy_from_slice(trainer);
+        }
+
+        ProgramRam {
+            data,
+            battery_size: rom.program_nvram_size,
+        }
+    }
+
+    /**
+     * index is relative to $6000
+     */
+    pub fn read(&self, index: usize) -> u8 {
+        if self.data.is_empty() {
+            OPEN_BUS
+        } else {
+            self.data[index % self.data.len()]
+        }
+    }
+
+    pub fn write(&mut self, index: usize, value: u8) {
+        if !self.data.is_empty() {
+            let length = self.data.len();
+            self.data[index % length] = value;
+        }
+    }
+
+    pub fn battery_ram(&mut self) -> Option<&mut [u8]> {
+        (self.battery_size > 0).then(|| &mut self.data[..self.battery_size])
+    }
+}
+
+/**
+ * Builds the cartridge for the given ROM's mapper
+ */
+pub fn new(rom: Rom) -> Box<dyn Cartridge> {
+    match rom.mapper {
+        Mapper::Zero => Box::new(Nrom::new(rom)),
+        Mapper::One => Box::new(Mmc1::new(rom)),
+        Mapper::Two => Box::new(Uxrom::new(rom)),
+        Mapper::Three => Box::new(Cnrom::new(rom)),
+        Mapper::Four => Box::new(Mmc3::new(rom)),
+        Mapper::Seven => Box::new(Axrom::new(rom)),
+        Mapper::SixtySix => Box::new(Gxrom::new(rom)),
+    }
+}
+
+/**
+ * Boards without a write-enable on their PRG-ROM have the ROM drive the data
+ * bus at the same time as the CPU, on writes to [$8000, $FFFF].
+ * The value that reaches the mapper is the AND of both.
+ */
+fn bus_conflict(rom_value: u8, value: u8) -> u8 {
+    rom_value & value
+}
+
+/**
+ * NES 2.0 submappers for the discrete-logic boards say whether the board has
+ * bus conflicts (1: no; 2: yes). Submapper 0 leaves it to the default for the mapper.
+ */
+fn has_bus_conflicts(submapper: u8, default: bool) -> bool {
+    match submapper {
+        1 => false,
+        2 => true,
+        _ => default,
+    }
+}
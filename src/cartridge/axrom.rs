@@ -0,0 +1,164 @@
+use crate::{
+    cartridge::{self, Cartridge, ChrMemory, ProgramRam},
+    rom::{Mirroring, Rom},
+};
+
+const PROGRAM_RAM_START: u16 = 0x6000;
+const PROGRAM_RAM_END: u16 = 0x7FFF;
+const PROGRAM_ROM_START: u16 = 0x8000;
+const PROGRAM_ROM_END: u16 = 0xFFFF;
+
+const PROGRAM_BANK_SIZE: usize = 0x8000;
+
+/**
+ * Mapper 7. Writes to [$8000, $FFFF] select a 32KB PRG-ROM bank, and which
+ * nametable is used for single-screen mirroring:
+ *
+ * 7  bit  0
+ * ---- ----
+ * xxxM xPPP
+ *    |  |||
+ *    |  +++- 32KB PRG-ROM bank at $8000
+ *    +------ Nametable (0: lower; 1: upper)
+ *
+ * Only some AxROM boards have bus conflicts, and games made for the others
+ * write values that would conflict, so they're only emulated when the NES 2.0
+ * submapper asks for them.
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Axrom {
+    program_rom: Vec<u8>,
+    chr: ChrMemory,
+    program_ram: ProgramRam,
+    bus_conflicts: bool,
+
+    bank_select: u8,
+}
+
+impl Axrom {
+    pub fn new(rom: Rom) -> Self {
+        let program_ram = ProgramRam::new(&rom);
+        Axrom {
+            program_rom: rom.program_rom,
+            chr: ChrMemory::new(rom.chr_rom, rom.chr_ram_size + rom.chr_nvram_size),
+            program_ram,
+            bus_conflicts: cartridge::has_bus_conflicts(rom.submapper, false),
+
+            bank_select: 0,
+        }
+    }
+
+    /**
+     * Maps a CPU address in [$8000, $FFFF] to an index into program_rom
+     */
+    fn program_rom_index(&self, address: u16) -> usize {
+        let bank_count = (self.program_rom.len() / PROGRAM_BANK_SIZE).max(1);
+        let bank = (self.bank_select & 0b111) as usize % bank_count;
+        (bank * PROGRAM_BANK_SIZE + (address - PROGRAM_ROM_START) as usize) % self.program_rom.len()
+    }
+}
+
+impl Cartridge for Axrom {
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .read((address - PROGRAM_RAM_START) as usize),
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                self.program_rom[self.program_rom_index(address)]
+            }
+            _ => cartridge::OPEN_BUS,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .write((address - PROGRAM_RAM_START) as usize, value),
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                self.bank_select = if self.bus_conflicts {
+                    let rom_value = self.program_rom[self.program_rom_index(address)];
+                    cartridge::bus_conflict(rom_value, value)
+                } else {
+                    value
+                };
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr.read(address as usize)
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        self.chr.write(address as usize, value)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.bank_select & 0b1_0000 == 0 {
+            Mirroring::SingleScreenLower
+        } else {
+            Mirroring::SingleScreenUpper
+        }
+    }
+
+    fn battery_ram(&mut self) -> Option<&mut [u8]> {
+        self.program_ram.battery_ram()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        cartridge::Cartridge,
+        rom::{Mapper, Mirroring, Rom},
+    };
+
+    use super::Axrom;
+
+    fn new_axrom() -> Axrom {
+        Axrom::new(Rom {
+            program_rom: (0..4u8).flat_map(|bank| vec![bank; 0x8000]).collect(),
+            chr_rom: vec![],
+            chr_ram_size: 0x2000,
+            mapper: Mapper::Seven,
+            ..Rom::new_empty()
+        })
+    }
+
+    #[test]
+    fn test_axrom_program_banks() {
+        let mut axrom = new_axrom();
+        assert_eq!(axrom.cpu_read(0x8000), 0);
+
+        axrom.cpu_write(0x8000, 2);
+        assert_eq!(axrom.cpu_read(0x8000), 2);
+        assert_eq!(axrom.cpu_read(0xFFFF), 2);
+
+        // Banks wrap around the PRG-ROM size
+        axrom.cpu_write(0x8000, 7);
+        assert_eq!(axrom.cpu_read(0x8000), 3);
+    }
+
+    #[test]
+    fn test_axrom_mirroring() {
+        let mut axrom = new_axrom();
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+
+        axrom.cpu_write(0x8000, 0b0001_0001);
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenUpper);
+        assert_eq!(axrom.cpu_read(0x8000), 1);
+
+        axrom.cpu_write(0x8000, 0b0000_0001);
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+    }
+
+    #[test]
+    fn test_axrom_unmapped_accesses() {
+        let mut axrom = new_axrom();
+        axrom.cpu_write(0x5000, 0x42);
+        assert_eq!(axrom.cpu_read(0x5000), 0);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{
+    cartridge::{self, Cartridge, ChrMemory, ProgramRam},
+    config::CHR_ROM_PAGE_SIZE,
+    rom::{Mirroring, Rom},
+};
+
+const PROGRAM_RAM_START: u16 = 0x6000;
+const PROGRAM_RAM_END: u16 = 0x7FFF;
+const PROGRAM_ROM_START: u16 = 0x8000;
+const PROGRAM_ROM_END: u16 = 0xFFFF;
+
+/**
+ * Mapper 3. Fixed 16KB or 32KB of PRG-ROM, like NROM. Writes to
+ * [$8000, $FFFF] select the 8KB CHR-ROM bank. Has bus conflicts, unless the
+ * NES 2.0 submapper says otherwise.
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Cnrom {
+    program_rom: Vec<u8>,
+    chr: ChrMemory,
+    program_ram: ProgramRam,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+
+    chr_bank: u8,
+}
+
+impl Cnrom {
+    pub fn new(rom: Rom) -> Self {
+        let program_ram = ProgramRam::new(&rom);
+        Cnrom {
+            program_rom: rom.program_rom,
+            chr: ChrMemory::new(rom.chr_rom, rom.chr_ram_size + rom.chr_nvram_size),
+            program_ram,
+            mirroring: rom.mirroring,
+            bus_conflicts: cartridge::has_bus_conflicts(rom.submapper, true),
+
+            chr_bank: 0,
+        }
+    }
+
+    /**
+     * Maps a CPU address in [$8000, $FFFF] to an index into program_rom.
+     * 16KB of PRG-ROM is mirrored at $C000.
+     */
+    fn program_rom_index(&self, address: u16) -> usize {
+        (address - PROGRAM_ROM_START) as usize % self.program_rom.len()
+    }
+
+    /**
+     * Maps a PPU address in [$0000, $1FFF] to an index into chr
+     */
+    fn chr_index(&self, address: u16) -> usize {
+        let bank_size = CHR_ROM_PAGE_SIZE as usize;
+        let bank_count = (self.chr.len() / bank_size).max(1);
+        let bank = self.chr_bank as usize % bank_count;
+        bank * bank_size + address as usize
+    }
+}
+
+impl Cartridge for Cnrom {
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .read((address - PROGRAM_RAM_START) as usize),
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                self.program_rom[self.program_rom_index(address)]
+            }
+            _ => cartridge::OPEN_BUS,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .write((address - PROGRAM_RAM_START) as usize, value),
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                self.chr_bank = if self.bus_conflicts {
+                    let rom_value = self.program_rom[self.program_rom_index(address)];
+                    cartridge::bus_conflict(rom_value, value)
+                } else {
+                    value
+                };
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr.read(self.chr_index(address))
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        self.chr.write(self.chr_index(address), value)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn battery_ram(&mut self) -> Option<&mut [u8]> {
+        self.program_ram.battery_ram()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        cartridge::Cartridge,
+        rom::{Mapper, Rom},
+    };
+
+    use super::Cnrom;
+
+    fn new_cnrom() -> Cnrom {
+        let mut program_rom = vec![0xFF; 0x4000];
+        program_rom[1] = 0x01;
+        Cnrom::new(Rom {
+            program_rom,
+            chr_rom: (0..4u8).flat_map(|bank| vec![bank; 0x2000]).collect(),
+            mapper: Mapper::Three,
+            ..Rom::new_empty()
+        })
+    }
+
+    #[test]
+    fn test_cnrom_chr_banks() {
+        let mut cnrom = new_cnrom();
+        assert_eq!(cnrom.ppu_read(0x0000), 0);
+
+        cnrom.cpu_write(0x8000, 3);
+        assert_eq!(cnrom.ppu_read(0x0000), 3);
+        assert_eq!(cnrom.ppu_read(0x1FFF), 3);
+
+        // CHR-ROM can't be written
+        cnrom.ppu_write(0x0000, 0x42);
+        assert_eq!(cnrom.ppu_read(0x0000), 3);
+    }
+
+    #[test]
+    fn test_cnrom_bus_conflict() {
+        let mut cnrom = new_cnrom();
+
+        // ROM drives $01 at $8001, so 2 & 1 = 0
+        cnrom.cpu_write(0x8001, 2);
+        assert_eq!(cnrom.ppu_read(0x0000), 0);
+        cnrom.cpu_write(0x8001, 3);
+        assert_eq!(cnrom.ppu_read(0x0000), 1);
+
+        // 16KB of PRG-ROM is mirrored at $C000, bus conflicts included
+        cnrom.cpu_write(0xC001, 2);
+        assert_eq!(cnrom.ppu_read(0x0000), 0);
+        cnrom.cpu_write(0xC002, 2);
+        assert_eq!(cnrom.ppu_read(0x0000), 2);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    cartridge::{self, Cartridge, ChrMemory, ProgramRam},
+    config::CHR_ROM_PAGE_SIZE,
+    rom::{Mirroring, Rom},
+};
+
+const PROGRAM_RAM_START: u16 = 0x6000;
+const PROGRAM_RAM_END: u16 = 0x7FFF;
+const PROGRAM_ROM_START: u16 = 0x8000;
+const PROGRAM_ROM_END: u16 = 0xFFFF;
+
+const PROGRAM_BANK_SIZE: usize = 0x8000;
+
+/**
+ * Mapper 66. Writes to [$8000, $FFFF] select a 32KB PRG-ROM bank and an 8KB
+ * CHR-ROM bank. Has bus conflicts.
+ *
+ * 7  bit  0
+ * ---- ----
+ * xxPP xxCC
+ *   ||   ||
+ *   ||   ++- 8KB CHR-ROM bank at PPU $0000
+ *   ++------ 32KB PRG-ROM bank at CPU $8000
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Gxrom {
+    program_rom: Vec<u8>,
+    chr: ChrMemory,
+    program_ram: ProgramRam,
+    mirroring: Mirroring,
+
+    bank_select: u8,
+}
+
+impl Gxrom {
+    pub fn new(rom: Rom) -> Self {
+        let program_ram = ProgramRam::new(&rom);
+        Gxrom {
+            program_rom: rom.program_rom,
+            chr: ChrMemory::new(rom.chr_rom, rom.chr_ram_size + rom.chr_nvram_size),
+            program_ram,
+            mirroring: rom.mirroring,
+
+            bank_select: 0,
+        }
+    }
+
+    /**
+     * Maps a CPU address in [$8000, $FFFF] to an index into program_rom
+     */
+    fn program_rom_index(&self, address: u16) -> usize {
+        let bank_count = (self.program_rom.len() / PROGRAM_BANK_SIZE).max(1);
+        let bank = ((self.bank_select >> 4) & 0b11) as usize % bank_count;
+        (bank * PROGRAM_BANK_SIZE + (address - PROGRAM_ROM_START) as usize) % self.program_rom.len()
+    }
+
+    /**
+     * Maps a PPU address in [$0000, $1FFF] to an index into chr
+     */
+    fn chr_index(&self, address: u16) -> usize {
+        let bank_size = CHR_ROM_PAGE_SIZE as usize;
+        let bank_count = (self.chr.len() / bank_size).max(1);
+        let bank = (self.bank_select & 0b11) as usize % bank_count;
+        bank * bank_size + address as usize
+    }
+}
+
+impl Cartridge for Gxrom {
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .read((address - PROGRAM_RAM_START) as usize),
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                self.program_rom[self.program_rom_index(address)]
+            }
+            _ => cartridge::OPEN_BUS,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .write((address - PROGRAM_RAM_START) as usize, value),
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                let rom_value = self.program_rom[self.program_rom_index(address)];
+                self.bank_select = cartridge::bus_conflict(rom_value, value);
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr.read(self.chr_index(address))
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        self.chr.write(self.chr_index(address), value)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn battery_ram(&mut self) -> Option<&mut [u8]> {
+        self.program_ram.battery_ram()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        cartridge::Cartridge,
+        rom::{Mapper, Rom},
+    };
+
+    use super::Gxrom;
+
+    /**
+     * Each PRG-ROM bank starts with its bank number, followed by $FF
+     */
+    fn new_gxrom() -> Gxrom {
+        let mut program_rom = vec![0xFF; 4 * 0x8000];
+        for bank in 0..4u8 {
+            program_rom[bank as usize * 0x8000] = bank;
+        }
+        Gxrom::new(Rom {
+            program_rom,
+            chr_rom: (0..4u8).flat_map(|bank| vec![bank; 0x2000]).collect(),
+            mapper: Mapper::SixtySix,
+            ..Rom::new_empty()
+        })
+    }
+
+    #[test]
+    fn test_gxrom_banks() {
+        let mut gxrom = new_gxrom();
+        gxrom.cpu_write(0x8001, 0b0010_0001);
+
+        assert_eq!(gxrom.cpu_read(0x8000), 2);
+        assert_eq!(gxrom.cpu_read(0xFFFF), 0xFF);
+        assert_eq!(gxrom.ppu_read(0x0000), 1);
+        assert_eq!(gxrom.ppu_read(0x1FFF), 1);
+
+        gxrom.cpu_write(0x8001, 0b0011_0011);
+        assert_eq!(gxrom.cpu_read(0x8000), 3);
+        assert_eq!(gxrom.ppu_read(0x0000), 3);
+    }
+
+    #[test]
+    fn test_gxrom_bus_conflict() {
+        let mut gxrom = new_gxrom();
+        gxrom.cpu_write(0x8001, 0b0010_0000);
+        assert_eq!(gxrom.cpu_read(0x8000), 2);
+
+        // ROM drives $02 at $8000, so $33 & $02 selects PRG bank 0 and CHR bank 2
+        gxrom.cpu_write(0x8000, 0b0011_0011);
+        assert_eq!(gxrom.cpu_read(0x8000), 0);
+        assert_eq!(gxrom.ppu_read(0x0000), 2);
+    }
+}
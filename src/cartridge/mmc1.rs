@@ -0,0 +1,296 @@
+use crate::{
+    cartridge::{self, Cartridge, ChrMemory, ProgramRam},
+    config::PROGRAM_ROM_PAGE_SIZE,
+    rom::{Mirroring, Rom},
+};
+
+const PROGRAM_RAM_START: u16 = 0x6000;
+const PROGRAM_RAM_END: u16 = 0x7FFF;
+const PROGRAM_ROM_START: u16 = 0x8000;
+const PROGRAM_ROM_END: u16 = 0xFFFF;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+// Boards with 512KB of PRG-ROM (SUROM) select the 256KB half with CHR bank bit 4
+const PROGRAM_ROM_OUTER_BANK_SIZE: usize = 0x40000;
+
+const SHIFT_REGISTER_RESET: u8 = 0b1_0000;
+
+/**
+ * Mapper 1. Registers are loaded one bit at a time through a serial shift
+ * register, by writes to [$8000, $FFFF]:
+ *
+ * $8000-$9FFF: Control
+ * 4bit0
+ * -----
+ * CPPMM
+ * |||||
+ * |||++- Mirroring (0: one-screen, lower bank; 1: one-screen, upper bank;
+ * |||               2: vertical; 3: horizontal)
+ * |++--- PRG-ROM bank mode (0, 1: switch 32KB at $8000, ignoring low bit of bank number;
+ * |                         2: fix first bank at $8000 and switch 16KB bank at $C000;
+ * |                         3: fix last bank at $C000 and switch 16KB bank at $8000)
+ * +----- CHR-ROM bank mode (0: switch 8KB at a time; 1: switch two separate 4KB banks)
+ *
+ * $A000-$BFFF: CHR bank 0
+ * $C000-$DFFF: CHR bank 1 (ignored in 8KB mode)
+ * $E000-$FFFF: PRG bank (bits 0-3), PRG-RAM disable (bit 4)
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Mmc1 {
+    program_rom: Vec<u8>,
+    chr: ChrMemory,
+    program_ram: ProgramRam,
+
+    shift_register: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    program_bank: u8,
+}
+
+impl Mmc1 {
+    pub fn new(rom: Rom) -> Self {
+        let program_ram = ProgramRam::new(&rom);
+        Mmc1 {
+            program_rom: rom.program_rom,
+            chr: ChrMemory::new(rom.chr_rom, rom.chr_ram_size + rom.chr_nvram_size),
+            program_ram,
+
+            shift_register: SHIFT_REGISTER_RESET,
+            control: 0b0_1100,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            program_bank: 0,
+        }
+    }
+
+    /**
+     * Shifts bit 0 of the value into the shift register.
+     * On the fifth write, copies the shift register to the register selected
+     * by bits 13-14 of the address.
+     * Writes with bit 7 set reset the shift register instead.
+     */
+    fn write_to_shift_register(&mut self, address: u16, value: u8) {
+        if value & 0b1000_0000 != 0 {
+            self.shift_register = SHIFT_REGISTER_RESET;
+            self.control |= 0b0_1100;
+            return;
+        }
+
+        let register_full = self.shift_register & 1 != 0;
+        self.shift_register = (self.shift_register >> 1) | ((value & 1) << 4);
+
+        if register_full {
+            let register_value = self.shift_register;
+            match address {
+                0x8000..=0x9FFF => self.control = register_value,
+                0xA000..=0xBFFF => self.chr_bank_0 = register_value,
+                0xC000..=0xDFFF => self.chr_bank_1 = register_value,
+                0xE000..=0xFFFF => self.program_bank = register_value,
+                _ => panic!("Invalid MMC1 register address: {:04X}", address),
+            }
+            self.shift_register = SHIFT_REGISTER_RESET;
+        }
+    }
+
+    fn program_ram_enabled(&self) -> bool {
+        self.program_bank & 0b1_0000 == 0
+    }
+
+    /**
+     * Maps a CPU address in [$8000, $FFFF] to an index into program_rom
+     */
+    fn program_rom_index(&self, address: u16) -> usize {
+        let bank_size = PROGRAM_ROM_PAGE_SIZE as usize;
+        let outer_bank_count = (self.program_rom.len() / PROGRAM_ROM_OUTER_BANK_SIZE).max(1);
+        let outer_bank_start = if outer_bank_count > 1 && self.chr_bank_0 & 0b1_0000 != 0 {
+            PROGRAM_ROM_OUTER_BANK_SIZE
+        } else {
+            0
+        };
+        let inner_size = self.program_rom.len().min(PROGRAM_ROM_OUTER_BANK_SIZE);
+        let bank_count = inner_size / bank_size;
+
+        let selected_bank = (self.program_bank & 0x0F) as usize;
+        let offset = (address - PROGRAM_ROM_START) as usize;
+        let bank_in_window = offset / bank_size;
+        let bank_offset = offset % bank_size;
+
+        let bank = match ((self.control >> 2) & 0b11, bank_in_window) {
+            (0 | 1, _) => (selected_bank & !1) + bank_in_window,
+            (2, 0) => 0,
+            (2, _) => selected_bank,
+            (3, 0) => selected_bank,
+            (3, _) => bank_count - 1,
+            _ => unreachable!(),
+        };
+
+        outer_bank_start + (bank % bank_count) * bank_size + bank_offset
+    }
+
+    /**
+     * Maps a PPU address in [$0000, $1FFF] to an index into chr
+     */
+    fn chr_index(&self, address: u16) -> usize {
+        let bank_count = (self.chr.len() / CHR_BANK_SIZE).max(1);
+        let address = address as usize;
+
+        let bank = if self.control & 0b1_0000 == 0 {
+            // 8KB mode: low bit of the bank number is ignored
+            (self.chr_bank_0 & !1) as usize + address / CHR_BANK_SIZE
+        } else if address < CHR_BANK_SIZE {
+            self.chr_bank_0 as usize
+        } else {
+            self.chr_bank_1 as usize
+        };
+
+        (bank % bank_count) * CHR_BANK_SIZE + address % CHR_BANK_SIZE
+    }
+}
+
+impl Cartridge for Mmc1 {
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => {
+                if self.program_ram_enabled() {
+                    self.program_ram
+                        .read((address - PROGRAM_RAM_START) as usize)
+                } else {
+                    cartridge::OPEN_BUS
+                }
+            }
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                self.program_rom[self.program_rom_index(address)]
+            }
+            _ => cartridge::OPEN_BUS,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => {
+                if self.program_ram_enabled() {
+                    self.program_ram
+                        .write((address - PROGRAM_RAM_START) as usize, value);
+                }
+            }
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => self.write_to_shift_register(address, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr.read(self.chr_index(address))
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        self.chr.write(self.chr_index(address), value)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            3 => Mirroring::Horizontal,
+            _ => unreachable!(),
+        }
+    }
+
+    fn battery_ram(&mut self) -> Option<&mut [u8]> {
+        self.program_ram.battery_ram()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        cartridge::Cartridge,
+        config::PROGRAM_ROM_PAGE_SIZE,
+        rom::{Mapper, Mirroring, Rom},
+    };
+
+    use super::Mmc1;
+
+    /**
+     * 8 PRG-ROM banks, each filled with its bank number
+     */
+    fn new_mmc1() -> Mmc1 {
+        let program_rom = (0..8u8)
+            .flat_map(|bank| vec![bank; PROGRAM_ROM_PAGE_SIZE as usize])
+            .collect();
+        Mmc1::new(Rom {
+            program_rom,
+            chr_rom: (0..4u8).flat_map(|bank| vec![bank; 0x1000]).collect(),
+            mirroring: Mirroring::Horizontal,
+            mapper: Mapper::One,
+            ..Rom::new_empty()
+        })
+    }
+
+    fn write_register(mmc1: &mut Mmc1, address: u16, value: u8) {
+        for bit in 0..5 {
+            mmc1.cpu_write(address, (value >> bit) & 1);
+        }
+    }
+
+    #[test]
+    fn test_mmc1_program_bank_modes() {
+        let mut mmc1 = new_mmc1();
+
+        // Power-on mode 3: last bank fixed at $C000
+        write_register(&mut mmc1, 0xE000, 2);
+        assert_eq!(mmc1.cpu_read(0x8000), 2);
+        assert_eq!(mmc1.cpu_read(0xC000), 7);
+
+        // Mode 2: first bank fixed at $8000
+        write_register(&mut mmc1, 0x8000, 0b0_1000);
+        assert_eq!(mmc1.cpu_read(0x8000), 0);
+        assert_eq!(mmc1.cpu_read(0xC000), 2);
+
+        // Mode 0: 32KB, low bit ignored
+        write_register(&mut mmc1, 0x8000, 0b0_0000);
+        write_register(&mut mmc1, 0xE000, 5);
+        assert_eq!(mmc1.cpu_read(0x8000), 4);
+        assert_eq!(mmc1.cpu_read(0xC000), 5);
+    }
+
+    #[test]
+    fn test_mmc1_chr_bank_modes() {
+        let mut mmc1 = new_mmc1();
+
+        // 4KB mode
+        write_register(&mut mmc1, 0x8000, 0b1_0000);
+        write_register(&mut mmc1, 0xA000, 3);
+        write_register(&mut mmc1, 0xC000, 1);
+        assert_eq!(mmc1.ppu_read(0x0000), 3);
+        assert_eq!(mmc1.ppu_read(0x1000), 1);
+
+        // 8KB mode
+        write_register(&mut mmc1, 0x8000, 0b0_0000);
+        assert_eq!(mmc1.ppu_read(0x0000), 2);
+        assert_eq!(mmc1.ppu_read(0x1000), 3);
+    }
+
+    #[test]
+    fn test_mmc1_unmapped_accesses() {
+        let mut mmc1 = new_mmc1();
+        mmc1.cpu_write(0x5000, 0x42);
+        assert_eq!(mmc1.cpu_read(0x5000), 0);
+    }
+
+    #[test]
+    fn test_mmc1_reset_and_mirroring() {
+        let mut mmc1 = new_mmc1();
+
+        // A write with bit 7 set discards the partially loaded value
+        mmc1.cpu_write(0x8000, 1);
+        mmc1.cpu_write(0x8000, 1);
+        mmc1.cpu_write(0x8000, 0x80);
+
+        write_register(&mut mmc1, 0x8000, 0b0_1110);
+        assert_eq!(mmc1.mirroring(), Mirroring::Vertical);
+        write_register(&mut mmc1, 0x8000, 0b0_1101);
+        assert_eq!(mmc1.mirroring(), Mirroring::SingleScreenUpper);
+    }
+}
@@ -0,0 +1,412 @@
+use crate::{
+    cartridge::{self, Cartridge, ChrMemory, ProgramRam},
+    rom::{Mirroring, Rom},
+};
+
+const PROGRAM_RAM_START: u16 = 0x6000;
+const PROGRAM_RAM_END: u16 = 0x7FFF;
+const PROGRAM_ROM_START: u16 = 0x8000;
+const PROGRAM_ROM_END: u16 = 0xFFFF;
+const PROGRAM_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+
+const PPU_A12_MASK: u16 = 0x1000;
+/**
+ * A12 has to have been low for about 3 CPU cycles before a rise is counted
+ */
+const PPU_A12_FILTER_DOTS: u32 = 10;
+
+/**
+ * Mapper 4. Registers are selected by the address range, and whether the
+ * address is even or odd:
+ *
+ * $8000 (even): Bank select
+ * 7  bit  0
+ * ---- ----
+ * CPMx xRRR
+ * |||   |||
+ * |||   +++- Bank register to update on the next write to bank data (R0-R7)
+ * ||+------- Nothing on MMC3
+ * |+-------- PRG-ROM bank mode (0: $8000 swappable, $C000 fixed to second-last bank;
+ * |                             1: $C000 swappable, $8000 fixed to second-last bank)
+ * +--------- CHR A12 inversion (0: two 2KB banks at $0000, four 1KB banks at $1000;
+ *                               1: two 2KB banks at $1000, four 1KB banks at $0000)
+ *
+ * $8001 (odd): Bank data
+ * $A000 (even): Mirroring (0: vertical; 1: horizontal)
+ * $A001 (odd): PRG-RAM protect (bit 7: enable, bit 6: deny writes)
+ * $C000 (even): IRQ latch
+ * $C001 (odd): IRQ reload
+ * $E000 (even): IRQ disable, and acknowledge any pending IRQ
+ * $E001 (odd): IRQ enable
+ *
+ * The IRQ counter is clocked on each rise of PPU address line A12. When
+ * background and sprites use different pattern tables, that happens once per
+ * scanline. Rises after A12 was only low briefly, e.g. between 8x16 sprites
+ * from both tables, or around a $2007 access, are filtered out.
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Mmc3 {
+    program_rom: Vec<u8>,
+    chr: ChrMemory,
+    program_ram: ProgramRam,
+    four_screen: bool,
+
+    bank_select: u8,
+    bank_registers: [u8; 8],
+    mirroring: Mirroring,
+    program_ram_protect: u8,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    ppu_a12: bool,
+    ppu_a12_low_dots: u32,
+}
+
+impl Mmc3 {
+    pub fn new(rom: Rom) -> Self {
+        let program_ram = ProgramRam::new(&rom);
+        Mmc3 {
+            program_rom: rom.program_rom,
+            chr: ChrMemory::new(rom.chr_rom, rom.chr_ram_size + rom.chr_nvram_size),
+            program_ram,
+            four_screen: rom.mirroring == Mirroring::FourScreen,
+
+            bank_select: 0,
+            bank_registers: [0; 8],
+            mirroring: rom.mirroring,
+            program_ram_protect: 0b1000_0000,
+
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            ppu_a12: false,
+            ppu_a12_low_dots: 0,
+        }
+    }
+
+    fn write_to_register(&mut self, address: u16, value: u8) {
+        let even = address & 1 == 0;
+        match (address, even) {
+            (0x8000..=0x9FFF, true) => self.bank_select = value,
+            (0x8000..=0x9FFF, false) => {
+                self.bank_registers[(self.bank_select & 0b111) as usize] = value
+            }
+            (0xA000..=0xBFFF, true) => {
+                if !self.four_screen {
+                    self.mirroring = if value & 1 == 0 {
+                        Mirroring::Vertical
+                    } else {
+                        Mirroring::Horizontal
+                    };
+                }
+            }
+            (0xA000..=0xBFFF, false) => self.program_ram_protect = value,
+            (0xC000..=0xDFFF, true) => self.irq_latch = value,
+            (0xC000..=0xDFFF, false) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (0xE000..=0xFFFF, true) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            (0xE000..=0xFFFF, false) => self.irq_enabled = true,
+            _ => panic!("Invalid MMC3 register address: {:04X}", address),
+        }
+    }
+
+    /**
+     * Clocks the IRQ counter on a rising edge of PPU A12, if it was low for
+     * long enough
+     */
+    fn watch_ppu_a12(&mut self, address: u16) {
+        let a12 = address & PPU_A12_MASK != 0;
+        if a12 && !self.ppu_a12 && self.ppu_a12_low_dots >= PPU_A12_FILTER_DOTS {
+            self.clock_irq_counter();
+        }
+        if a12 != self.ppu_a12 {
+            self.ppu_a12_low_dots = 0;
+        }
+        self.ppu_a12 = a12;
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn program_ram_enabled(&self) -> bool {
+        self.program_ram_protect & 0b1000_0000 != 0
+    }
+
+    fn program_ram_writable(&self) -> bool {
+        self.program_ram_enabled() && self.program_ram_protect & 0b0100_0000 == 0
+    }
+
+    /**
+     * Maps a CPU address in [$8000, $FFFF] to an index into program_rom
+     */
+    fn program_rom_index(&self, address: u16) -> usize {
+        // An 8KB PRG-ROM has no second-last bank; the fixed banks both wrap to bank 0
+        let bank_count = (self.program_rom.len() / PROGRAM_BANK_SIZE).max(1);
+        let second_last_bank = bank_count.saturating_sub(2);
+        let last_bank = bank_count - 1;
+        let r6 = (self.bank_registers[6] & 0b0011_1111) as usize;
+        let r7 = (self.bank_registers[7] & 0b0011_1111) as usize;
+
+        let offset = (address - PROGRAM_ROM_START) as usize;
+        let program_mode = self.bank_select & 0b0100_0000 != 0;
+        let bank = match (offset / PROGRAM_BANK_SIZE, program_mode) {
+            (0, false) => r6,
+            (0, true) => second_last_bank,
+            (1, _) => r7,
+            (2, false) => second_last_bank,
+            (2, true) => r6,
+            (3, _) => last_bank,
+            _ => unreachable!(),
+        };
+
+        (bank % bank_count) * PROGRAM_BANK_SIZE + offset % PROGRAM_BANK_SIZE
+    }
+
+    /**
+     * Maps a PPU address in [$0000, $1FFF] to an index into chr
+     */
+    fn chr_index(&self, address: u16) -> usize {
+        let bank_count = (self.chr.len() / CHR_BANK_SIZE).max(1);
+
+        // With A12 inversion, the 2KB and 1KB halves swap places
+        let chr_inversion = self.bank_select & 0b1000_0000 != 0;
+        let address = if chr_inversion {
+            address ^ PPU_A12_MASK
+        } else {
+            address
+        } as usize;
+
+        let slot = address / CHR_BANK_SIZE;
+        let bank = match slot {
+            0 | 1 => (self.bank_registers[0] & !1) as usize + slot,
+            2 | 3 => (self.bank_registers[1] & !1) as usize + slot - 2,
+            4..=7 => self.bank_registers[slot - 2] as usize,
+            _ => unreachable!(),
+        };
+
+        (bank % bank_count) * CHR_BANK_SIZE + address % CHR_BANK_SIZE
+    }
+}
+
+impl Cartridge for Mmc3 {
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => {
+                if self.program_ram_enabled() {
+                    self.program_ram
+                        .read((address - PROGRAM_RAM_START) as usize)
+                } else {
+                    cartridge::OPEN_BUS
+                }
+            }
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                self.program_rom[self.program_rom_index(address)]
+            }
+            _ => cartridge::OPEN_BUS,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => {
+                if self.program_ram_writable() {
+                    self.program_ram
+                        .write((address - PROGRAM_RAM_START) as usize, value);
+                }
+            }
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => self.write_to_register(address, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.watch_ppu_a12(address);
+        self.chr.read(self.chr_index(address))
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        self.watch_ppu_a12(address);
+        self.chr.write(self.chr_index(address), value)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn tick_ppu_dot(&mut self) {
+        if !self.ppu_a12 {
+            self.ppu_a12_low_dots = self.ppu_a12_low_dots.saturating_add(1);
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn battery_ram(&mut self) -> Option<&mut [u8]> {
+        self.program_ram.battery_ram()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        cartridge::Cartridge,
+        rom::{Mapper, Mirroring, Rom},
+    };
+
+    use super::Mmc3;
+
+    /**
+     * 8 8KB PRG-ROM banks and 8 1KB CHR-ROM banks, each filled with its bank number
+     */
+    fn new_mmc3() -> Mmc3 {
+        Mmc3::new(Rom {
+            program_rom: (0..8u8).flat_map(|bank| vec![bank; 0x2000]).collect(),
+            chr_rom: (0..8u8).flat_map(|bank| vec![bank; 0x0400]).collect(),
+            mirroring: Mirroring::Vertical,
+            mapper: Mapper::Four,
+            ..Rom::new_empty()
+        })
+    }
+
+    fn tick_dots(mmc3: &mut Mmc3, dots: u32) {
+        for _ in 0..dots {
+            mmc3.tick_ppu_dot();
+        }
+    }
+
+    /**
+     * Simulates one scanline of background fetches at $0000 and sprite fetches at $1000
+     */
+    fn scanline(mmc3: &mut Mmc3) {
+        mmc3.ppu_read(0x0000);
+        tick_dots(mmc3, 256);
+        mmc3.ppu_read(0x1000);
+        tick_dots(mmc3, 8);
+        mmc3.ppu_read(0x1010);
+        tick_dots(mmc3, 77);
+    }
+
+    #[test]
+    fn test_mmc3_program_banks() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0x8000, 6);
+        mmc3.cpu_write(0x8001, 3);
+        mmc3.cpu_write(0x8000, 7);
+        mmc3.cpu_write(0x8001, 4);
+
+        assert_eq!(mmc3.cpu_read(0x8000), 3);
+        assert_eq!(mmc3.cpu_read(0xA000), 4);
+        assert_eq!(mmc3.cpu_read(0xC000), 6);
+        assert_eq!(mmc3.cpu_read(0xE000), 7);
+
+        mmc3.cpu_write(0x8000, 0b0100_0000);
+        assert_eq!(mmc3.cpu_read(0x8000), 6);
+        assert_eq!(mmc3.cpu_read(0xC000), 3);
+    }
+
+    #[test]
+    fn test_mmc3_single_program_bank() {
+        let mut mmc3 = Mmc3::new(Rom {
+            program_rom: vec![0x42; 0x2000],
+            mapper: Mapper::Four,
+            ..Rom::new_empty()
+        });
+        mmc3.cpu_write(0x8000, 0b0100_0110);
+        mmc3.cpu_write(0x8001, 3);
+
+        for address in [0x8000, 0xA000, 0xC000, 0xE000] {
+            assert_eq!(mmc3.cpu_read(address), 0x42);
+        }
+    }
+
+    #[test]
+    fn test_mmc3_unmapped_accesses() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0x5000, 0x42);
+        assert_eq!(mmc3.cpu_read(0x5000), 0);
+    }
+
+    #[test]
+    fn test_mmc3_chr_inversion() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0x8000, 0);
+        mmc3.cpu_write(0x8001, 2);
+        mmc3.cpu_write(0x8000, 2);
+        mmc3.cpu_write(0x8001, 5);
+
+        assert_eq!(mmc3.ppu_read(0x0400), 3);
+        assert_eq!(mmc3.ppu_read(0x1000), 5);
+
+        mmc3.cpu_write(0x8000, 0b1000_0000);
+        assert_eq!(mmc3.ppu_read(0x1400), 3);
+        assert_eq!(mmc3.ppu_read(0x0000), 5);
+    }
+
+    #[test]
+    fn test_mmc3_scanline_irq() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0xC000, 2); // latch
+        mmc3.cpu_write(0xC001, 0); // reload
+        mmc3.cpu_write(0xE001, 0); // enable
+
+        scanline(&mut mmc3); // reload to 2
+        scanline(&mut mmc3); // 1
+        assert!(!mmc3.irq());
+        scanline(&mut mmc3); // 0
+        assert!(mmc3.irq());
+
+        mmc3.cpu_write(0xE000, 0); // acknowledge
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn test_mmc3_filters_short_a12_low() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0xC000, 1); // latch
+        mmc3.cpu_write(0xC001, 0); // reload
+        mmc3.cpu_write(0xE001, 0); // enable
+        scanline(&mut mmc3); // reload to 1
+
+        // A $2007 read from $0000 during the sprite fetches only drops A12
+        // for a few dots
+        mmc3.ppu_read(0x0000);
+        mmc3.ppu_read(0x1000);
+        tick_dots(&mut mmc3, 2);
+        mmc3.ppu_read(0x0000);
+        tick_dots(&mut mmc3, 4);
+        mmc3.ppu_read(0x1020);
+        assert!(!mmc3.irq());
+
+        // So does switching between 8x16 sprites in both tables
+        mmc3.ppu_read(0x0030);
+        tick_dots(&mut mmc3, 6);
+        mmc3.ppu_read(0x1030);
+        assert!(!mmc3.irq());
+
+        scanline(&mut mmc3); // 0
+        assert!(mmc3.irq());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{
+    cartridge::{self, Cartridge, ChrMemory, ProgramRam},
+    config::PROGRAM_ROM_PAGE_SIZE,
+    rom::{Mirroring, Rom},
+};
+
+const PROGRAM_RAM_START: u16 = 0x6000;
+const PROGRAM_RAM_END: u16 = 0x7FFF;
+const PROGRAM_ROM_START: u16 = 0x8000;
+const PROGRAM_ROM_END: u16 = 0xFFFF;
+
+/**
+ * Mapper 0. No bank switching: 16KB (NROM-128) or 32KB (NROM-256) of PRG-ROM,
+ * and 8KB of CHR-ROM. 16KB of PRG-ROM is mirrored at $C000.
+ * Some boards (e.g. Family BASIC) have 8KB of PRG-RAM at $6000.
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Nrom {
+    program_rom: Vec<u8>,
+    chr: ChrMemory,
+    program_ram: ProgramRam,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(rom: Rom) -> Self {
+        let program_ram = ProgramRam::new(&rom);
+        Nrom {
+            program_rom: rom.program_rom,
+            chr: ChrMemory::new(rom.chr_rom, rom.chr_ram_size + rom.chr_nvram_size),
+            program_ram,
+            mirroring: rom.mirroring,
+        }
+    }
+}
+
+impl Cartridge for Nrom {
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .read((address - PROGRAM_RAM_START) as usize),
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                let rom_address = address - PROGRAM_ROM_START;
+                let single_page_program_rom =
+                    self.program_rom.len() as u16 == PROGRAM_ROM_PAGE_SIZE;
+
+                let first_mirror_rom_address =
+                    if single_page_program_rom && rom_address >= PROGRAM_ROM_PAGE_SIZE {
+                        rom_address % PROGRAM_ROM_PAGE_SIZE
+                    } else {
+                        rom_address
+                    };
+
+                self.program_rom[first_mirror_rom_address as usize]
+            }
+            _ => cartridge::OPEN_BUS,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .write((address - PROGRAM_RAM_START) as usize, value),
+            // No registers, so writes to ROM are ignored
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {}
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr.read(address as usize)
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        self.chr.write(address as usize, value)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn battery_ram(&mut self) -> Option<&mut [u8]> {
+        self.program_ram.battery_ram()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        cartridge::{self, Cartridge},
+        rom::Rom,
+    };
+
+    use super::Nrom;
+
+    /**
+     * Builds an iNES file with the given number of 16KB PRG-ROM banks.
+     * Each PRG-ROM bank is filled with its bank number.
+     */
+    fn new_nrom_bytes(program_rom_banks: u8) -> Vec<u8> {
+        let mut rom_bytes = vec![0x4E, 0x45, 0x53, 0x1A, program_rom_banks, 1, 0, 0];
+        rom_bytes.resize(16, 0);
+        for bank in 0..program_rom_banks {
+            rom_bytes.extend(vec![bank; 0x4000]);
+        }
+        rom_bytes.extend(vec![0; 0x2000]);
+        rom_bytes
+    }
+
+    #[test]
+    fn test_nrom_128_mirrors_program_rom() {
+        let mut rom_bytes = new_nrom_bytes(1);
+        rom_bytes[16] = 0x11;
+        rom_bytes[16 + 0x3FFF] = 0x22;
+        let mut cartridge = cartridge::new(Rom::new(&rom_bytes).unwrap());
+
+        assert_eq!(cartridge.cpu_read(0x8000), 0x11);
+        assert_eq!(cartridge.cpu_read(0xBFFF), 0x22);
+        assert_eq!(cartridge.cpu_read(0xC000), 0x11);
+        assert_eq!(cartridge.cpu_read(0xFFFF), 0x22);
+    }
+
+    #[test]
+    fn test_nrom_loads_trainer() {
+        let mut rom_bytes = new_nrom_bytes(1);
+        rom_bytes[6] |= 0b0000_0100;
+        rom_bytes.splice(16..16, vec![0xAA; 512]);
+        let mut cartridge = cartridge::new(Rom::new(&rom_bytes).unwrap());
+
+        assert_eq!(cartridge.cpu_read(0x6FFF), 0);
+        assert_eq!(cartridge.cpu_read(0x7000), 0xAA);
+        assert_eq!(cartridge.cpu_read(0x71FF), 0xAA);
+        assert_eq!(cartridge.cpu_read(0x7200), 0);
+    }
+
+    #[test]
+    fn test_nrom_ignores_unmapped_accesses() {
+        let rom = Rom::new(&new_nrom_bytes(1)).unwrap();
+        let mut cartridge = cartridge::new(rom);
+
+        cartridge.cpu_write(0x8000, 0x42);
+        cartridge.cpu_write(0x5000, 0x42);
+        assert_eq!(cartridge.cpu_read(0x8000), 0);
+        assert_eq!(cartridge.cpu_read(0x5000), 0);
+    }
+
+    #[test]
+    fn test_nrom_program_ram_size() {
+        // 2KB is mirrored through $6000-$7FFF
+        let mut nrom = Nrom::new(Rom {
+            program_ram_size: 0x800,
+            ..Rom::new_empty()
+        });
+        nrom.cpu_write(0x6000, 0x42);
+        assert_eq!(nrom.cpu_read(0x6800), 0x42);
+        assert_eq!(nrom.battery_ram(), None);
+
+        let mut nrom = Nrom::new(Rom {
+            program_ram_size: 0,
+            ..Rom::new_empty()
+        });
+        nrom.cpu_write(0x6000, 0x42);
+        assert_eq!(nrom.cpu_read(0x6000), 0);
+
+        // Only the PRG-NVRAM is saved
+        let mut nrom = Nrom::new(Rom {
+            has_battery: true,
+            program_ram_size: 0x800,
+            program_nvram_size: 0x400,
+            ..Rom::new_empty()
+        });
+        assert_eq!(nrom.battery_ram().map(|ram| ram.len()), Some(0x400));
+    }
+
+    #[test]
+    fn test_nrom_256_maps_both_banks() {
+        let rom = Rom::new(&new_nrom_bytes(2)).unwrap();
+        let mut cartridge = cartridge::new(rom);
+
+        assert_eq!(cartridge.cpu_read(0x8000), 0);
+        assert_eq!(cartridge.cpu_read(0xBFFF), 0);
+        assert_eq!(cartridge.cpu_read(0xC000), 1);
+        assert_eq!(cartridge.cpu_read(0xFFFF), 1);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    cartridge::{self, Cartridge, ChrMemory, ProgramRam},
+    config::PROGRAM_ROM_PAGE_SIZE,
+    rom::{Mirroring, Rom},
+};
+
+const PROGRAM_RAM_START: u16 = 0x6000;
+const PROGRAM_RAM_END: u16 = 0x7FFF;
+const PROGRAM_ROM_START: u16 = 0x8000;
+const PROGRAM_ROM_END: u16 = 0xFFFF;
+const PROGRAM_ROM_FIXED_START: u16 = 0xC000;
+
+/**
+ * Mapper 2. Writes to [$8000, $FFFF] select the 16KB PRG-ROM bank at $8000.
+ * The last bank is fixed at $C000. Has bus conflicts, unless the NES 2.0
+ * submapper says otherwise.
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Uxrom {
+    program_rom: Vec<u8>,
+    chr: ChrMemory,
+    program_ram: ProgramRam,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+
+    program_bank: u8,
+}
+
+impl Uxrom {
+    pub fn new(rom: Rom) -> Self {
+        let program_ram = ProgramRam::new(&rom);
+        Uxrom {
+            program_rom: rom.program_rom,
+            chr: ChrMemory::new(rom.chr_rom, rom.chr_ram_size + rom.chr_nvram_size),
+            program_ram,
+            mirroring: rom.mirroring,
+            bus_conflicts: cartridge::has_bus_conflicts(rom.submapper, true),
+
+            program_bank: 0,
+        }
+    }
+
+    /**
+     * Maps a CPU address in [$8000, $FFFF] to an index into program_rom
+     */
+    fn program_rom_index(&self, address: u16) -> usize {
+        let bank_size = PROGRAM_ROM_PAGE_SIZE as usize;
+        let bank_count = self.program_rom.len() / bank_size;
+        let bank = if address < PROGRAM_ROM_FIXED_START {
+            self.program_bank as usize % bank_count
+        } else {
+            bank_count - 1
+        };
+
+        bank * bank_size + (address as usize % bank_size)
+    }
+}
+
+impl Cartridge for Uxrom {
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .read((address - PROGRAM_RAM_START) as usize),
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                self.program_rom[self.program_rom_index(address)]
+            }
+            _ => cartridge::OPEN_BUS,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            PROGRAM_RAM_START..=PROGRAM_RAM_END => self
+                .program_ram
+                .write((address - PROGRAM_RAM_START) as usize, value),
+            PROGRAM_ROM_START..=PROGRAM_ROM_END => {
+                self.program_bank = if self.bus_conflicts {
+                    let rom_value = self.program_rom[self.program_rom_index(address)];
+                    cartridge::bus_conflict(rom_value, value)
+                } else {
+                    value
+                };
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr.read(address as usize)
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        self.chr.write(address as usize, value)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn battery_ram(&mut self) -> Option<&mut [u8]> {
+        self.program_ram.battery_ram()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        cartridge::Cartridge,
+        rom::{Mapper, Mirroring, Rom},
+    };
+
+    use super::Uxrom;
+
+    #[test]
+    fn test_uxrom_loads_trainer() {
+        // Even if the header declares no PRG-RAM
+        let mut uxrom = Uxrom::new(Rom {
+            mapper: Mapper::Two,
+            trainer: Some(vec![0xAA; 512]),
+            program_ram_size: 0,
+            ..Rom::new_empty()
+        });
+
+        assert_eq!(uxrom.cpu_read(0x6FFF), 0);
+        assert_eq!(uxrom.cpu_read(0x7000), 0xAA);
+        assert_eq!(uxrom.cpu_read(0x71FF), 0xAA);
+    }
+
+    #[test]
+    fn test_uxrom_unmapped_accesses() {
+        let mut uxrom = Uxrom::new(Rom {
+            mapper: Mapper::Two,
+            ..Rom::new_empty()
+        });
+        uxrom.cpu_write(0x5000, 0x42);
+        assert_eq!(uxrom.cpu_read(0x5000), 0);
+    }
+
+    #[test]
+    fn test_uxrom_ines_has_no_program_ram() {
+        let mut rom_bytes = vec![0x4E, 0x45, 0x53, 0x1A, 2, 0, 0x20, 0];
+        rom_bytes.resize(16, 0);
+        rom_bytes.extend(vec![0; 2 * 0x4000]);
+        let mut uxrom = Uxrom::new(Rom::new(&rom_bytes).unwrap());
+
+        uxrom.cpu_write(0x6000, 0x42);
+        assert_eq!(uxrom.cpu_read(0x6000), 0);
+        assert_eq!(uxrom.battery_ram(), None);
+    }
+
+    #[test]
+    fn test_uxrom_bus_conflict() {
+        let mut program_rom: Vec<u8> = (0..4u8).flat_map(|bank| vec![bank; 0x4000]).collect();
+        program_rom[0x3FFE] = 0xFF;
+        program_rom[0x3FFF] = 0x01;
+        let mut uxrom = Uxrom::new(Rom {
+            program_rom,
+            chr_rom: vec![0; 0x2000],
+            mirroring: Mirroring::Vertical,
+            mapper: Mapper::Two,
+            ..Rom::new_empty()
+        });
+
+        uxrom.cpu_write(0xBFFE, 2);
+        assert_eq!(uxrom.cpu_read(0x8000), 2);
+        assert_eq!(uxrom.cpu_read(0xC000), 3);
+
+        // ROM drives $01 at $BFFF while still in bank 0, so 2 & 1 = 0
+        uxrom.cpu_write(0x8000, 0);
+        uxrom.cpu_write(0xBFFF, 2);
+        assert_eq!(uxrom.cpu_read(0x8000), 0);
+    }
+}
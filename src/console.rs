@@ -1,9 +1,533 @@
-use crate::{bus::Bus, cpu::Cpu, ppu::Ppu, rom::Rom};
+use crate::{
+    apu::Apu,
+    bus::{self, Bus},
+    cartridge::{self, Cartridge},
+    cpu::{self, Cpu},
+    frame::Frame,
+    instruction::{self, Instruction},
+    ppu::{self, Ppu},
+    region::Region,
+    rom::Rom,
+    util::Error,
+};
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+// Cycles the CPU is stalled for while the DMC reads a sample byte. It's
+// between 1 and 4 on hardware, depending on what the CPU was doing.
+const DMC_STALL_CYCLES: u32 = 4;
+
+#[derive(Debug)]
 pub struct Console {
     pub cpu: Cpu,
     pub bus: Bus,
     pub ppu: Ppu,
-    pub rom: Rom,
+    pub apu: Apu,
+    pub cartridge: Box<dyn Cartridge>,
+    pub region: Region,
+}
+
+impl Console {
+    /**
+     * Inserts the ROM and resets the console
+     */
+    pub fn new(rom: Rom, region: Region) -> Self {
+        let mut console = Console {
+            cpu: Cpu::new(),
+            bus: Bus::new(),
+            ppu: Ppu::new(region),
+            apu: Apu::new(region),
+            cartridge: cartridge::new(rom),
+            region,
+        };
+        cpu::reset_interrupt(&mut console);
+        console
+    }
+
+    /**
+     * Runs until the PPU completes a frame, then returns it
+     */
+    pub fn run_frame(&mut self) -> Result<&Frame, Error> {
+        self.run_frame_with(|_, _| {})
+    }
+
+    /**
+     * Like run_frame, calling the callback before each instruction runs
+     */
+    pub fn run_frame_with<CallbackFn>(&mut self, mut callback: CallbackFn) -> Result<&Frame, Error>
+    where
+        CallbackFn: FnMut(&mut Console, &Instruction),
+    {
+        let frame_count = self.ppu.frame_count();
+        while self.ppu.frame_count() == frame_count {
+            self.step_instruction_with(&mut callback)?;
+        }
+        Ok(&self.ppu.frame)
+    }
+
+    /**
+     * Runs instructions until at least the given number of CPU cycles have passed.
+     * Returns the number of cycles that actually passed.
+     */
+    pub fn step_cycles(&mut self, cycles: u32) -> Result<u32, Error> {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.step_instruction()?;
+        }
+        Ok(elapsed)
+    }
+
+    /**
+     * Runs one instruction, after taking any pending interrupt.
+     * Returns the number of CPU cycles it took, including the interrupt's.
+     */
+    pub fn step_instruction(&mut self) -> Result<u32, Error> {
+        self.step_instruction_with(|_, _| {})
+    }
+
+    /**
+     * Like step_instruction, calling the callback before the instruction runs
+     */
+    pub fn step_instruction_with<CallbackFn>(
+        &mut self,
+        mut callback: CallbackFn,
+    ) -> Result<u32, Error>
+    where
+        CallbackFn: FnMut(&mut Console, &Instruction),
+    {
+        let interrupt_cycles = if ppu::poll_nmi_status(&mut self.ppu) {
+            cpu::nmi_interrupt(self)
+        } else if cpu::poll_irq_status(self) {
+            cpu::irq_interrupt(self)
+        } else {
+            0
+        };
+
+        let opcode = bus::read_u8(self, self.cpu.pc);
+        let Some(instruction) = instruction::find(opcode) else {
+            return Err(format!("Unimplemented opcode: 0x{:02X}", opcode).into());
+        };
+
+        callback(self, instruction);
+
+        // Most instructions access memory on their last cycle, so catch the PPU
+        // and APU up to it first. Register accesses then see the right cycle.
+        let cycles = instruction.cycles as u32;
+        self.ppu
+            .tick_cpu_cycles(self.cartridge.as_mut(), cycles - 1);
+        self.apu.tick(cycles - 1);
+        cpu::step(self, instruction)?;
+        self.ppu.tick_cpu_cycles(self.cartridge.as_mut(), 1);
+        self.apu.tick(1);
+
+        Ok(interrupt_cycles + cycles + self.fetch_dmc_sample())
+    }
+
+    /**
+     * Fills the DMC's sample buffer over the bus, if it's empty. The CPU is
+     * stalled while the DMC uses the bus.
+     * Returns the number of cycles the CPU was stalled for.
+     */
+    fn fetch_dmc_sample(&mut self) -> u32 {
+        let Some(address) = self.apu.dmc.pending_read() else {
+            return 0;
+        };
+        let sample = bus::read_u8(self, address);
+        self.apu.dmc.load_sample(sample);
+
+        self.ppu
+            .tick_cpu_cycles(self.cartridge.as_mut(), DMC_STALL_CYCLES);
+        self.apu.tick(DMC_STALL_CYCLES);
+        DMC_STALL_CYCLES
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{bus, console::Console, region::Region, rom::Rom};
+
+    /**
+     * Builds an NROM-128 console running the given program from $8000.
+     * The NMI handler is at $8010.
+     */
+    fn new_console(program: &[u8], nmi_handler: &[u8]) -> Console {
+        let mut program_rom = vec![0xEA; 0x4000];
+        program_rom[..program.len()].copy_from_slice(program);
+        program_rom[0x10..0x10 + nmi_handler.len()].copy_from_slice(nmi_handler);
+        program_rom[0x3FFA..].copy_from_slice(&[0x10, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+        let mut rom_bytes = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0];
+        rom_bytes.resize(16, 0);
+        rom_bytes.extend(program_rom);
+        rom_bytes.extend(vec![0; 0x2000]);
+        Console::new(Rom::new(&rom_bytes).unwrap(), Region::Ntsc)
+    }
+
+    #[test]
+    fn test_step_instruction() {
+        // LDA #$42
+        let mut console = new_console(&[0xA9, 0x42], &[]);
+        assert_eq!(console.cpu.pc, 0x8000);
+
+        assert_eq!(console.step_instruction().unwrap(), 2);
+        assert_eq!(console.cpu.a, 0x42);
+        assert_eq!(console.cpu.pc, 0x8002);
+    }
+
+    #[test]
+    fn test_step_cycles() {
+        // JMP $8000
+        let mut console = new_console(&[0x4C, 0x00, 0x80], &[]);
+        assert_eq!(console.step_cycles(10).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_run_frame() {
+        // LDA #$80; STA $2000; JMP $8005
+        // NMI: INC $00; RTI
+        let mut console = new_console(
+            &[0xA9, 0x80, 0x8D, 0x00, 0x20, 0x4C, 0x05, 0x80],
+            &[0xE6, 0x00, 0x40],
+        );
+
+        for frame in 0..3 {
+            assert_eq!(console.ppu.frame_count(), frame);
+            console.run_frame().unwrap();
+        }
+        assert_eq!(console.ppu.frame_count(), 3);
+        assert_eq!(bus::read_u8(&mut console, 0x0000), 3);
+    }
+
+    #[test]
+    fn test_interrupt_cycles() {
+        // NOPs, with a NOP as the NMI handler
+        let mut console = new_console(&[], &[]);
+        console.ppu.nmi_interrupt = true;
+        assert_eq!(console.step_instruction().unwrap(), 7 + 2);
+        assert_eq!(console.cpu.pc, 0x8011);
+
+        assert_eq!(console.step_instruction().unwrap(), 2);
+
+        console.ppu.nmi_interrupt = true;
+        assert_eq!(console.step_cycles(1).unwrap(), 7 + 2);
+    }
+
+    #[test]
+    fn test_irq_cycles() {
+        // CLI, then NOPs until the APU's frame IRQ, whose handler is the CLI
+        let mut console = new_console(&[0x58], &[]);
+        let mut cycles = console.step_instruction().unwrap();
+        while cycles == 2 {
+            cycles = console.step_instruction().unwrap();
+        }
+        assert_eq!(cycles, 7 + 2);
+        assert_eq!(console.cpu.pc, 0x8001);
+    }
+
+    #[test]
+    fn test_dmc_fetch_stalls_cpu() {
+        // LDA #$10; STA $4015
+        let mut console = new_console(&[0xA9, 0x10, 0x8D, 0x15, 0x40], &[]);
+        assert_eq!(console.step_instruction().unwrap(), 2);
+
+        // Enabling the DMC fetches the first byte of its sample straight away
+        assert_eq!(console.step_instruction().unwrap(), 4 + 4);
+        assert_eq!(console.apu.dmc.pending_read(), None);
+    }
+
+    /**
+     * Failing checks jump here with their status in A
+     */
+    const REPORT: u16 = 0x8003;
+    const BODY_START: u16 = 0x8100;
+    const DATA_START: u16 = 0x8800;
+    /**
+     * NOPs, ending in an RTS
+     */
+    const SLED_START: u16 = 0x9000;
+    const SLED_LENGTH: u16 = 0x1000;
+
+    /**
+     * Builds a test program that reports its result the way blargg's test ROMs
+     * do: the status at $6000, $80 while running, with the signature DE B0 61
+     * at $6001 and an empty message at $6004. The body runs from $8100 with
+     * interrupts disabled, and passes with status 0 if it runs to the end.
+     * The data is at $8800.
+     */
+    fn blargg_program(body: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut program = vec![
+            0x4C, 0x09, 0x80, // JMP $8009
+            0x8D, 0x00, 0x60, // REPORT: STA $6000
+            0x4C, 0x06, 0x80, // JMP $8006
+            0x78, // SEI
+        ];
+        for (address, value) in [
+            (0x6000, 0x80),
+            (0x6001, 0xDE),
+            (0x6002, 0xB0),
+            (0x6003, 0x61),
+        ] {
+            program.extend(store(address, value));
+        }
+        program.extend(store(0x6004, 0));
+        program.extend([0x4C, 0x00, 0x81]); // JMP $8100
+
+        program.resize((BODY_START - 0x8000) as usize, 0xEA);
+        program.extend(body);
+        program.extend(fail(0)); // Passed
+        assert!(program.len() <= (DATA_START - 0x8000) as usize);
+        program.resize((DATA_START - 0x8000) as usize, 0xEA);
+        program.extend(data);
+        assert!(program.len() <= (SLED_START - 0x8000) as usize);
+        program.resize((SLED_START - 0x8000 + SLED_LENGTH) as usize, 0xEA);
+        program.push(0x60); // RTS
+        program
+    }
+
+    /**
+     * Runs a blargg_program until it reports its status
+     */
+    fn run_blargg_program(program: &[u8]) -> u8 {
+        let mut console = new_console(program, &[]);
+        for _ in 0..60 {
+            console.run_frame().unwrap();
+            let signature =
+                [0x6001, 0x6002, 0x6003].map(|address| bus::read_u8(&mut console, address));
+            let status = bus::read_u8(&mut console, 0x6000);
+            if signature == [0xDE, 0xB0, 0x61] && status < 0x80 {
+                return status;
+            }
+        }
+        panic!("The test program didn't finish");
+    }
+
+    /**
+     * LDA #value; STA address
+     */
+    fn store(address: u16, value: u8) -> Vec<u8> {
+        let [low, high] = address.to_le_bytes();
+        vec![0xA9, value, 0x8D, low, high]
+    }
+
+    /**
+     * LDA #status; JMP REPORT
+     */
+    fn fail(status: u8) -> Vec<u8> {
+        let [low, high] = REPORT.to_le_bytes();
+        vec![0xA9, status, 0x4C, low, high]
+    }
+
+    /**
+     * Reads $4015, and fails with the status unless the masked bits are as expected
+     */
+    fn expect_apu_status(mask: u8, expected: u8, status: u8) -> Vec<u8> {
+        let mut code = vec![
+            0xAD, 0x15, 0x40, // LDA $4015
+            0x29, mask, // AND #mask
+            0xC9, expected, // CMP #expected
+            0xF0, 0x05, // BEQ +5
+        ];
+        code.extend(fail(status));
+        code
+    }
+
+    /**
+     * Waits for exactly the given number of cycles, which can't be 1. Calls
+     * into the NOP sled rather than looping, so taken branches don't count.
+     */
+    fn delay(mut cycles: u32) -> Vec<u8> {
+        let mut code = vec![];
+        if cycles % 2 == 1 {
+            code.extend([0x24, 0x00]); // BIT $00
+            cycles -= 3;
+        }
+        // JSR into the sled, NOPs to its RTS: 12 cycles, plus 2 per NOP
+        while cycles >= 14 {
+            let nops = ((cycles - 12) / 2).min(SLED_LENGTH as u32) as u16;
+            let [low, high] = (SLED_START + SLED_LENGTH - nops).to_le_bytes();
+            code.extend([0x20, low, high]);
+            cycles -= 12 + 2 * nops as u32;
+        }
+        code.extend(vec![0xEA; cycles as usize / 2]); // NOP
+        code
+    }
+
+    #[test]
+    fn test_delay() {
+        for cycles in [2, 3, 5, 13, 14, 15, 8204, 8205, 8206, 29831, 400_000] {
+            let mut console = new_console(&blargg_program(&delay(cycles), &[]), &[]);
+            while console.cpu.pc != BODY_START {
+                console.step_instruction().unwrap();
+            }
+
+            let end = BODY_START + delay(cycles).len() as u16;
+            let mut elapsed = 0;
+            while console.cpu.pc != end {
+                elapsed += console.step_instruction().unwrap();
+            }
+            assert_eq!(elapsed, cycles);
+        }
+    }
+
+    /**
+     * Like blargg's apu_test 1-len_ctr, on pulse 1
+     */
+    #[test]
+    fn test_apu_length_counter() {
+        let mut body = vec![];
+        body.extend(store(0x4015, 0x01));
+        body.extend(store(0x4000, 0x10));
+        body.extend(store(0x4017, 0x40));
+
+        // 2) Loading the length counter makes the channel active in $4015
+        body.extend(store(0x4003, 0x18));
+        body.extend(expect_apu_status(0x01, 0x01, 2));
+
+        // 3) A length of 2 runs out on the second half frame
+        body.extend(delay(16000));
+        body.extend(expect_apu_status(0x01, 0x01, 3));
+        body.extend(delay(16000));
+        body.extend(expect_apu_status(0x01, 0x00, 3));
+
+        // 4) Writing $80 to $4017 clocks it straight away
+        body.extend(store(0x4003, 0x18));
+        body.extend(store(0x4017, 0xC0));
+        body.extend(store(0x4017, 0xC0));
+        body.extend(expect_apu_status(0x01, 0x00, 4));
+
+        // 5) Writing $00 doesn't
+        body.extend(store(0x4003, 0x18));
+        body.extend(store(0x4017, 0x00));
+        body.extend(store(0x4017, 0x00));
+        body.extend(expect_apu_status(0x01, 0x01, 5));
+
+        // 6) Disabling the channel clears it
+        body.extend(store(0x4015, 0x00));
+        body.extend(expect_apu_status(0x01, 0x00, 6));
+
+        // 7) It can't be loaded while disabled
+        body.extend(store(0x4003, 0x18));
+        body.extend(expect_apu_status(0x01, 0x00, 7));
+
+        // 8) The halt bit stops it being clocked
+        body.extend(store(0x4015, 0x01));
+        body.extend(store(0x4000, 0x30));
+        body.extend(store(0x4003, 0x18));
+        body.extend(store(0x4017, 0xC0));
+        body.extend(store(0x4017, 0xC0));
+        body.extend(expect_apu_status(0x01, 0x01, 8));
+
+        assert_eq!(run_blargg_program(&blargg_program(&body, &[])), 0);
+    }
+
+    /**
+     * Like blargg's apu_test 2-len_table. Loads each length, then counts the
+     * $4017 writes it takes to run out.
+     */
+    #[test]
+    fn test_apu_length_table() {
+        let mut body = vec![];
+        body.extend(store(0x4015, 0x01));
+        body.extend(store(0x4000, 0x10));
+        body.extend([
+            0xA2, 0x00, // LDX #0
+            0x8A, // next: TXA
+            0x0A, 0x0A, 0x0A, // ASL A; ASL A; ASL A
+            0x8D, 0x03, 0x40, // STA $4003
+            0xA0, 0x00, // LDY #0
+            0xAD, 0x15, 0x40, // count: LDA $4015
+            0x29, 0x01, // AND #$01
+            0xF0, 0x08, // BEQ done
+            0xA9, 0xC0, // LDA #$C0
+            0x8D, 0x17, 0x40, // STA $4017
+            0xC8, // INY
+            0xD0, 0xF1, // BNE count
+            0x98, // done: TYA
+            0xDD, 0x00, 0x88, // CMP $8800,X
+            0xF0, 0x05, // BEQ ok
+        ]);
+        body.extend(fail(2));
+        body.extend([
+            0xE8, // ok: INX
+            0xE0, 0x20, // CPX #32
+            0xD0, 0xD8, // BNE next
+        ]);
+
+        let lengths = [
+            10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20,
+            96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+        ];
+        assert_eq!(run_blargg_program(&blargg_program(&body, &lengths)), 0);
+    }
+
+    /**
+     * Like blargg's apu_test 3-irq_flag
+     */
+    #[test]
+    fn test_apu_irq_flag() {
+        // Starts the sequence in the given mode, and waits past its fourth step
+        let run_sequence = |mode: u8| {
+            let mut code = store(0x4017, mode);
+            code.extend(delay(32000));
+            code
+        };
+
+        let mut body = vec![];
+        // 2) Not set with IRQs inhibited
+        body.extend(run_sequence(0x40));
+        body.extend(expect_apu_status(0x40, 0x00, 2));
+
+        // 3) Not set in 5-step mode
+        body.extend(run_sequence(0x80));
+        body.extend(expect_apu_status(0x40, 0x00, 3));
+
+        // 4) Set in 4-step mode, and 5) reading it clears it
+        body.extend(run_sequence(0x00));
+        body.extend(expect_apu_status(0x40, 0x40, 4));
+        body.extend(expect_apu_status(0x40, 0x00, 5));
+
+        // 6) Writing $00 or $80 to $4017 doesn't clear it, 7) $40 or $C0 does
+        for (mode, expected, status) in [
+            (0x00, 0x40, 6),
+            (0x80, 0x40, 6),
+            (0x40, 0x00, 7),
+            (0xC0, 0x00, 7),
+        ] {
+            body.extend(run_sequence(0x00));
+            body.extend(store(0x4017, mode));
+            body.extend(expect_apu_status(0x40, expected, status));
+        }
+
+        assert_eq!(run_blargg_program(&blargg_program(&body, &[])), 0);
+    }
+
+    /**
+     * Like blargg's apu_test 6-irq_flag_timing. The flag is set 29828 cycles
+     * after the sequence restarts, which is 3 or 4 cycles after the $4017
+     * write, depending on whether it's on an even or odd cycle.
+     */
+    #[test]
+    fn test_apu_irq_flag_timing() {
+        // Whether the flag is set when $4015 is read the given number of cycles
+        // after the write
+        let flag_set_after = |parity_delay: u32, cycles: u32| {
+            let mut body = delay(parity_delay);
+            body.extend(store(0x4017, 0x00));
+            // LDA $4015 reads on its fourth cycle
+            body.extend(delay(cycles - 4));
+            body.extend(expect_apu_status(0x40, 0x40, 2));
+            run_blargg_program(&blargg_program(&body, &[])) == 0
+        };
+
+        // Shifting the write by a cycle changes its parity, so the first read
+        // that sees the flag is 29828 + 3 or 4 cycles after it
+        for (parity_delay, first_cycle) in [(2, 29832), (3, 29831)] {
+            assert!(!flag_set_after(parity_delay, first_cycle - 1));
+            assert!(flag_set_after(parity_delay, first_cycle));
+        }
+    }
+
+    #[test]
+    fn test_unimplemented_opcode() {
+        let mut console = new_console(&[0x02], &[]);
+        assert!(console.step_instruction().is_err());
+    }
 }
@@ -10,7 +10,13 @@ use bitflags::bitflags;
 const ROM_START: u16 = 0xC000;
 const STACK_PAGE_ADDRESS: u16 = 0x0100;
 
+const NMI_INTERRUPT_VECTOR_ADDRESS: u16 = 0xFFFA;
 const RESET_INTERRUPT_VECTOR_ADDRESS: u16 = 0xFFFC;
+const IRQ_INTERRUPT_VECTOR_ADDRESS: u16 = 0xFFFE;
+/**
+ * Pushing PC and the flags, then reading the vector, takes as long as BRK
+ */
+pub const INTERRUPT_CYCLES: u32 = 7;
 
 bitflags! {
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -97,7 +103,7 @@ pub fn reset_interrupt(console: &mut Console) {
     console.cpu.pc = bus::read_u16(console, RESET_INTERRUPT_VECTOR_ADDRESS);
 }
 
-pub fn nmi_interrupt(console: &mut Console) {
+pub fn nmi_interrupt(console: &mut Console) -> u32 {
     push_stack_u16(console, console.cpu.pc);
     let mut flags = console.cpu.flags.clone();
     flags = flags.union(Flags::BREAK).difference(Flags::BREAK_2);
@@ -105,8 +111,38 @@ pub fn nmi_interrupt(console: &mut Console) {
     push_stack_u8(console, flags.bits());
     console.cpu.flags.insert(Flags::INTERRUPT_DISABLE);
 
-    console.ppu.tick(2 * 3);
-    console.cpu.pc = bus::read_u16(console, 0xFFFA);
+    console
+        .ppu
+        .tick_cpu_cycles(console.cartridge.as_mut(), INTERRUPT_CYCLES);
+    console.apu.tick(INTERRUPT_CYCLES);
+    console.cpu.pc = bus::read_u16(console, NMI_INTERRUPT_VECTOR_ADDRESS);
+    INTERRUPT_CYCLES
+}
+
+/**
+ * Whether a maskable interrupt should be taken before the next instruction.
+ * The IRQ line is level-triggered: it stays asserted until the source is
+ * acknowledged, and is ignored while INTERRUPT_DISABLE is set.
+ */
+pub fn poll_irq_status(console: &Console) -> bool {
+    let irq_line = console.cartridge.irq() || console.apu.irq();
+    irq_line && !console.cpu.flags.contains(Flags::INTERRUPT_DISABLE)
+}
+
+pub fn irq_interrupt(console: &mut Console) -> u32 {
+    push_stack_u16(console, console.cpu.pc);
+    let mut flags = console.cpu.flags;
+    flags = flags.union(Flags::BREAK).difference(Flags::BREAK_2);
+
+    push_stack_u8(console, flags.bits());
+    console.cpu.flags.insert(Flags::INTERRUPT_DISABLE);
+
+    console
+        .ppu
+        .tick_cpu_cycles(console.cartridge.as_mut(), INTERRUPT_CYCLES);
+    console.apu.tick(INTERRUPT_CYCLES);
+    console.cpu.pc = bus::read_u16(console, IRQ_INTERRUPT_VECTOR_ADDRESS);
+    INTERRUPT_CYCLES
 }
 
 pub fn step(console: &mut Console, instruction: &Instruction) -> Result<(), Error> {
@@ -6,7 +6,7 @@ use crate::{
 
 pub fn trace(console: &mut Console, instruction: &Instruction) -> String {
     let instruction_bytes: Vec<u8> = (0..instruction.bytes as u16)
-        .map(|i| bus::read_u8(console, console.cpu.pc + i))
+        .map(|i| bus::peek_u8(console, console.cpu.pc + i))
         .collect();
     let instruction_bytes_string = instruction_bytes
         .iter()
@@ -27,7 +27,7 @@ pub fn trace(console: &mut Console, instruction: &Instruction) -> String {
             format!("{} ${:02X},Y", instruction.operation, instruction_bytes[1])
         }
         AddressingMode::Relative => {
-            let offset = bus::read_i8(console, console.cpu.pc + 1);
+            let offset = bus::peek_u8(console, console.cpu.pc + 1) as i8;
             let address = console.cpu.pc as i32 + 2 + offset as i32; // PC is incremented +2 during read
             format!("{} ${:02X}", instruction.operation, address)
         }
@@ -66,55 +66,52 @@ pub fn trace(console: &mut Console, instruction: &Instruction) -> String {
             },
             AddressingMode::ZeroPage => {
                 let address = instruction_bytes[1] as u16;
-                let value = bus::read_u8(console, address);
+                let value = bus::peek_u8(console, address);
                 format!(" = {:02X}", value)
             }
             AddressingMode::ZeroPageX => {
                 let address = instruction_bytes[1];
                 let address_x = address.wrapping_add(console.cpu.x);
-                let value = bus::read_u8(console, address_x as u16);
+                let value = bus::peek_u8(console, address_x as u16);
                 format!(" @ {:02X} = {:02X}", address_x, value)
             }
             AddressingMode::ZeroPageY => {
                 let address = instruction_bytes[1];
                 let address_y = address.wrapping_add(console.cpu.y);
-                let value = bus::read_u8(console, address_y as u16);
+                let value = bus::peek_u8(console, address_y as u16);
                 format!(" @ {:02X} = {:02X}", address_y, value)
             }
             AddressingMode::Absolute => match instruction.operation {
                 "JMP" | "JSR" => "".to_string(),
                 _ => {
                     let address = u16::from_le_bytes([instruction_bytes[1], instruction_bytes[2]]);
-                    let value = match address {
-                        0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 | 0x4014 => 0,
-                        _ => bus::read_u8(console, address),
-                    };
+                    let value = bus::peek_u8(console, address);
                     format!(" = {:02X}", value)
                 }
             },
             AddressingMode::AbsoluteX => {
                 let address = u16::from_le_bytes([instruction_bytes[1], instruction_bytes[2]]);
                 let address_x = address.wrapping_add(console.cpu.x as u16);
-                let value = bus::read_u8(console, address_x);
+                let value = bus::peek_u8(console, address_x);
                 format!(" @ {:04X} = {:02X}", address_x, value)
             }
             AddressingMode::AbsoluteY => {
                 let address = u16::from_le_bytes([instruction_bytes[1], instruction_bytes[2]]);
                 let address_y = address.wrapping_add(console.cpu.y as u16);
-                let value = bus::read_u8(console, address_y);
+                let value = bus::peek_u8(console, address_y);
                 format!(" @ {:04X} = {:02X}", address_y, value)
             }
             AddressingMode::Indirect => {
                 let indirect_address =
                     u16::from_le_bytes([instruction_bytes[1], instruction_bytes[2]]);
-                let address = bus::read_u16_wrap_page(console, indirect_address);
+                let address = bus::peek_u16_wrap_page(console, indirect_address);
                 format!(" = {:04X}", address)
             }
             AddressingMode::IndirectX => {
                 let mut indirect_address = instruction_bytes[1];
                 indirect_address = indirect_address.wrapping_add(console.cpu.x);
-                let address = bus::read_u16_wrap_page(console, indirect_address as u16);
-                let value = bus::read_u8(console, address);
+                let address = bus::peek_u16_wrap_page(console, indirect_address as u16);
+                let value = bus::peek_u8(console, address);
                 format!(
                     " @ {:02X} = {:04X} = {:02X}",
                     indirect_address, address, value
@@ -122,9 +119,9 @@ pub fn trace(console: &mut Console, instruction: &Instruction) -> String {
             }
             AddressingMode::IndirectY => {
                 let indirect_address = instruction_bytes[1];
-                let address = bus::read_u16_wrap_page(console, indirect_address as u16);
+                let address = bus::peek_u16_wrap_page(console, indirect_address as u16);
                 let address_y = address.wrapping_add(console.cpu.y as u16);
-                let value = bus::read_u8(console, address_y);
+                let value = bus::peek_u8(console, address_y);
                 format!(" = {:04X} @ {:04X} = {:02X}", address, address_y, value)
             }
             _ => "".to_string(),
@@ -142,3 +139,34 @@ pub fn trace(console: &mut Console, instruction: &Instruction) -> String {
         console.cpu.sp
     )
 }
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        console::Console, debug, instruction, ppu::StatusRegister, region::Region, rom::Rom,
+    };
+
+    #[test]
+    fn test_trace_has_no_side_effects() {
+        // LDA $2002; LDA $4015
+        let mut program = vec![0xAD, 0x02, 0x20, 0xAD, 0x15, 0x40];
+        program.resize(0x4000, 0xEA);
+        program[0x3FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+        let mut rom_bytes = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0];
+        rom_bytes.resize(16, 0);
+        rom_bytes.extend(program);
+        rom_bytes.extend(vec![0; 0x2000]);
+        let mut console = Console::new(Rom::new(&rom_bytes).unwrap(), Region::Ntsc);
+
+        console.ppu.status.insert(StatusRegister::VBLANK_STARTED);
+        console.apu.frame_counter.irq = true;
+
+        let trace = debug::trace(&mut console, instruction::find(0xAD).unwrap());
+        assert!(trace.contains("LDA $2002"));
+        console.cpu.pc = 0x8003;
+        debug::trace(&mut console, instruction::find(0xAD).unwrap());
+
+        assert!(console.ppu.status.contains(StatusRegister::VBLANK_STARTED));
+        assert!(console.apu.frame_counter.irq);
+    }
+}
@@ -0,0 +1,33 @@
+/**
+ * The PPU's output image, as RGB24
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Frame {
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    pub const WIDTH: usize = 256;
+    pub const HIGHT: usize = 240;
+
+    pub fn new() -> Self {
+        Frame {
+            data: vec![0; (Frame::WIDTH) * (Frame::HIGHT) * 3],
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, rgb: (u8, u8, u8)) {
+        let base = y * 3 * Frame::WIDTH + x * 3;
+        if base + 2 < self.data.len() {
+            self.data[base] = rgb.0;
+            self.data[base + 1] = rgb.1;
+            self.data[base + 2] = rgb.2;
+        }
+    }
+
+    #[cfg(test)]
+    pub fn get_pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        let base = y * 3 * Frame::WIDTH + x * 3;
+        (self.data[base], self.data[base + 1], self.data[base + 2])
+    }
+}
@@ -4,56 +4,27 @@ use sdl2::{
     pixels::PixelFormatEnum,
     render::{Canvas, TextureCreator},
     video::{Window, WindowContext},
-    EventPump,
+    EventPump, Sdl,
 };
 
-use crate::{palette, ppu::Ppu, util::Error};
+use nes::{frame::Frame, util::Error};
 
-const TILE_LENGTH: u16 = 8;
-const PATTERN_TABLE_TILE_LENGTH: u16 = TILE_LENGTH * 2;
 const SCREEN_WIDTH: u16 = 256;
 const SCREEN_HEIGHT: u16 = 240;
-const SCREEN_WIDTH_TILES: u16 = SCREEN_WIDTH / TILE_LENGTH;
-const SCREEN_HEIGHT_TILES: u16 = SCREEN_HEIGHT / TILE_LENGTH;
 
 const PIXEL_MULTIPLIER: u16 = 2;
 
-pub struct Frame {
-    pub data: Vec<u8>,
-}
-
-impl Frame {
-    const WIDTH: usize = 256;
-    const HIGHT: usize = 240;
-
-    pub fn new() -> Self {
-        Frame {
-            data: vec![0; (Frame::WIDTH) * (Frame::HIGHT) * 3],
-        }
-    }
-
-    pub fn set_pixel(&mut self, x: usize, y: usize, rgb: (u8, u8, u8)) {
-        let base = y * 3 * Frame::WIDTH + x * 3;
-        if base + 2 < self.data.len() {
-            self.data[base] = rgb.0;
-            self.data[base + 1] = rgb.1;
-            self.data[base + 2] = rgb.2;
-        }
-    }
-}
-
 pub struct Graphics {
-    frame: Frame,
     canvas: Canvas<Window>,
     texture_creator: TextureCreator<WindowContext>,
     event_pump: EventPump,
 }
 
 impl Graphics {
-    pub fn new() -> Result<Self, Error> {
-        let frame = Frame::new();
-
-        let sdl_context = sdl2::init()?;
+    /**
+     * With vsync, presenting a frame waits for the display's refresh
+     */
+    pub fn new(sdl_context: &Sdl, vsync: bool) -> Result<Self, Error> {
         let video_subsystem = sdl_context.video()?;
 
         let window_width = (SCREEN_WIDTH * PIXEL_MULTIPLIER) as u32;
@@ -63,86 +34,52 @@ impl Graphics {
             .position_centered()
             .build()?;
 
-        let canvas = window.into_canvas().present_vsync().build()?;
+        let mut canvas_builder = window.into_canvas();
+        if vsync {
+            canvas_builder = canvas_builder.present_vsync();
+        }
+        let canvas = canvas_builder.build()?;
         let event_pump = sdl_context.event_pump()?;
 
         let texture_creator = canvas.texture_creator();
 
         Ok(Graphics {
-            frame,
             canvas,
             texture_creator,
             event_pump,
         })
     }
 
-    pub fn render(&mut self, ppu: &Ppu) -> Result<(), Error> {
-        render_to_frame(ppu, &mut self.frame);
+    /**
+     * Presents the given frame to the window
+     */
+    pub fn render(&mut self, frame: &Frame) -> Result<(), Error> {
         let mut texture = self.texture_creator.create_texture_target(
             PixelFormatEnum::RGB24,
             SCREEN_WIDTH as u32,
             SCREEN_HEIGHT as u32,
         )?;
-        texture.update(None, &self.frame.data, 256 * 3)?;
+        texture.update(None, &frame.data, Frame::WIDTH * 3)?;
         self.canvas.copy(&texture, None, None)?;
         self.canvas.present();
 
-        loop {
-            for event in self.event_pump.poll_iter() {
-                match event {
-                    Event::Quit { .. }
+        Ok(())
+    }
+
+    /**
+     * Handles pending window events.
+     * Returns true if the user asked to quit
+     */
+    pub fn poll_quit(&mut self) -> bool {
+        self.event_pump.poll_iter().any(|event| {
+            matches!(
+                event,
+                Event::Quit { .. }
                     | Event::KeyDown {
                         keycode: Some(Keycode::Escape),
                         ..
-                    } => std::process::exit(0),
-                    _ => { /* do nothing */ }
-                }
-            }
-        }
-    }
-}
-
-/**
- * Mutates the given frame, rendering the PPU's output to it
- */
-fn render_to_frame(ppu: &Ppu, frame: &mut Frame) {
-    let pattern_table_offset = ppu.control.background_pattern_offset();
-
-    for tile_n in 0..(SCREEN_WIDTH_TILES * SCREEN_HEIGHT_TILES) {
-        // Get the nth tile's pattern table index from the nametable
-        let tile_pattern_n = ppu.vram[tile_n as usize] as u16;
-        let tile_x = tile_n % SCREEN_WIDTH_TILES;
-        let tile_y = tile_n / SCREEN_WIDTH_TILES;
-
-        let tile_pattern_data_start =
-            pattern_table_offset + tile_pattern_n * PATTERN_TABLE_TILE_LENGTH;
-        let tile_pattern_data_end = pattern_table_offset
-            + tile_pattern_n * PATTERN_TABLE_TILE_LENGTH
-            + PATTERN_TABLE_TILE_LENGTH
-            - 1;
-        let tile_pattern_data =
-            &ppu.chr_rom[tile_pattern_data_start as usize..=tile_pattern_data_end as usize];
-
-        // x and y are relative within tile_n
-        for y in 0..TILE_LENGTH {
-            let mut left_bit_row = tile_pattern_data[y as usize];
-            let mut right_bit_row = tile_pattern_data[y as usize + 8];
-
-            for x in (0..TILE_LENGTH).rev() {
-                let pixel_value = (left_bit_row & 0x01) << 1 | (right_bit_row & 0x01);
-                left_bit_row = left_bit_row >> 1;
-                right_bit_row = right_bit_row >> 1;
-                let rgb = match pixel_value {
-                    0 => palette::SYSTEM_PALLETE[0x01],
-                    1 => palette::SYSTEM_PALLETE[0x23],
-                    2 => palette::SYSTEM_PALLETE[0x27],
-                    3 => palette::SYSTEM_PALLETE[0x30],
-                    _ => panic!("can't be"),
-                };
-                let pixel_x = tile_x * TILE_LENGTH + x;
-                let pixel_y = tile_y * TILE_LENGTH + y;
-                frame.set_pixel(pixel_x as usize, pixel_y as usize, rgb);
-            }
-        }
+                    }
+            )
+        })
     }
 }
@@ -1,3 +1,5 @@
+use std::sync::OnceLock;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum AddressingMode {
     Immediate,
@@ -229,3 +231,14 @@ pub fn instructions() -> Vec<Instruction> {
         Instruction::new(0x8C, "STY", AddressingMode::Absolute, 3, 4),
     ]
 }
+
+/**
+ * The instruction for an opcode, or None if it isn't implemented
+ */
+pub fn find(opcode: u8) -> Option<&'static Instruction> {
+    static INSTRUCTIONS: OnceLock<Vec<Instruction>> = OnceLock::new();
+    INSTRUCTIONS
+        .get_or_init(instructions)
+        .iter()
+        .find(|instruction| instruction.opcode == opcode)
+}
@@ -0,0 +1,20 @@
+#![feature(bigint_helper_methods)]
+// Types are built with new() throughout, rather than Default
+#![allow(clippy::new_without_default)]
+pub mod apu;
+pub mod audio;
+pub mod bus;
+pub mod cartridge;
+mod config;
+pub mod console;
+pub mod cpu;
+pub mod debug;
+pub mod frame;
+pub mod instruction;
+mod palette;
+pub mod ppu;
+pub mod region;
+pub mod rom;
+pub mod save;
+pub mod util;
+pub mod wav;
@@ -1,93 +1,114 @@
-#![feature(bigint_helper_methods)]
-mod bus;
-mod config;
-mod console;
-mod cpu;
-mod debug;
 mod graphics;
-mod instruction;
-mod palette;
-mod ppu;
-mod rom;
-mod util;
+mod speaker;
 
-use cpu::Cpu;
 use graphics::Graphics;
-use instruction::Instruction;
+use nes::{
+    audio, console::Console, debug, region::Region, rom::Rom, save, util::Error, wav::WavWriter,
+};
 use simple_logger::SimpleLogger;
-use std::fs;
-use util::Error;
-
-use crate::{bus::Bus, console::Console, ppu::Ppu, rom::Rom};
-
-fn run_with_callbacks<PostResetFn, CallbackFn>(
-    console: &mut Console,
-    graphics: &mut Graphics,
-    instructions: &Vec<Instruction>,
-    mut post_reset: PostResetFn,
-    mut callback: CallbackFn,
-) -> Result<(), Error>
-where
-    PostResetFn: FnMut(&mut Console),
-    CallbackFn: FnMut(&mut Console, &Instruction),
-{
-    cpu::reset_interrupt(console);
-    post_reset(console);
-
-    loop {
-        let opcode = bus::read_u8(console, console.cpu.pc);
-        let instruction = instructions.iter().find(|&instr| instr.opcode == opcode);
-
-        let instruction = if instruction.is_none() {
-            todo!("Unimplemented opcode: 0x{:02X}", opcode);
-        } else {
-            instruction.unwrap()
-        };
-
-        if ppu::poll_nmi_status(&mut console.ppu) {
-            cpu::nmi_interrupt(console);
-            graphics.render(&mut console.ppu)?;
-        }
-
-        callback(console, instruction);
-        cpu::step(console, instruction)?;
-        console.ppu.tick(instruction.cycles as u32 * 3);
-    }
-}
+use speaker::Speaker;
+use std::{
+    env,
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
 
 fn main() -> Result<(), Error> {
     // Init logging
     SimpleLogger::new().init().unwrap();
 
     // Load ROM
-    // let rom_bytes = fs::read("roms/donkey_kong.nes")?;
-    let rom_bytes = fs::read("roms/nestest.nes")?;
+    // let rom_path = Path::new("roms/donkey_kong.nes");
+    let rom_path = Path::new("roms/nestest.nes");
+    let rom_bytes = fs::read(rom_path)?;
     let rom = Rom::new(&rom_bytes)?;
-    let ppu = Ppu::new(&rom);
+
+    // The ROM's region can be overridden with --region ntsc|pal|dendy
+    let region = match arg_value("--region") {
+        Some(name) => name.parse()?,
+        None => Region::from(rom.timing),
+    };
 
     // Init console
-    let instructions = instruction::instructions();
-    let mut console = Console {
-        cpu: Cpu::new(),
-        bus: Bus::new(),
-        ppu,
-        rom,
+    let mut console = Console::new(rom, region);
+    // console.cpu.pc = 0xC000;
+    log::info!("Running {} as {:?}", rom_path.display(), console.region);
+
+    // Load battery-backed save RAM
+    let save_path = save::save_path(rom_path);
+    save::load(console.cartridge.as_mut(), &save_path)?;
+
+    let sdl_context = sdl2::init()?;
+
+    // Init audio. It paces the emulation, or vsync does if there's no audio device.
+    let mut speaker = match Speaker::new(&sdl_context, audio::DEFAULT_SAMPLE_RATE) {
+        Ok(speaker) => {
+            console.apu.audio.set_sample_rate(speaker.sample_rate());
+            Some(speaker)
+        }
+        Err(error) => {
+            log::warn!("No audio output: {}", error);
+            None
+        }
     };
 
     // Init graphics
-    let mut graphics = Graphics::new()?;
+    let mut graphics = Graphics::new(&sdl_context, speaker.is_none())?;
+
+    // The audio can be recorded with --record-audio out.wav
+    let mut recorder = match arg_value("--record-audio") {
+        Some(path) => Some(WavWriter::create(
+            Path::new(&path),
+            console.apu.audio.sample_rate(),
+        )?),
+        None => None,
+    };
+
+    let result = run(&mut console, &mut graphics, &mut speaker, &mut recorder);
+
+    // Save and finish the recording even if the emulation failed, so the
+    // game's progress isn't lost, and the WAV file's header is complete.
+    // Neither is skipped if the other fails.
+    let finish_result = match recorder {
+        Some(recorder) => recorder.finish().map(|_| ()),
+        None => Ok(()),
+    };
+    let save_result = save::save(console.cartridge.as_mut(), &save_path);
+
+    result.and(save_result).and(finish_result)
+}
 
-    run_with_callbacks(
-        &mut console,
-        &mut graphics,
-        &instructions,
-        move |console| {
-            // console.cpu.pc = 0xC000
-        },
-        move |console, instruction| {
+/**
+ * Runs frames until the window is closed
+ */
+fn run(
+    console: &mut Console,
+    graphics: &mut Graphics,
+    speaker: &mut Option<Speaker>,
+    recorder: &mut Option<WavWriter<BufWriter<File>>>,
+) -> Result<(), Error> {
+    loop {
+        let frame = console.run_frame_with(|console, instruction| {
             println!("{}", debug::trace(console, instruction));
-        },
-    )?;
+        })?;
+        graphics.render(frame)?;
+        let samples = console.apu.audio.take_samples();
+        if let Some(recorder) = recorder {
+            recorder.write_samples(&samples)?;
+        }
+        if let Some(speaker) = speaker {
+            speaker.play(&samples)?;
+        }
+        if graphics.poll_quit() {
+            return Ok(());
+        }
+    }
+}
 
-    Ok(())
+/**
+ * The value following the given option in the command line arguments
+ */
+fn arg_value(option: &str) -> Option<String> {
+    env::args().skip_while(|arg| arg != option).nth(1)
 }
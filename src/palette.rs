@@ -1,3 +1,5 @@
+use std::sync::OnceLock;
+
 #[rustfmt::skip]
 pub static SYSTEM_PALLETE: [(u8,u8,u8); 64] = [
    (0x80, 0x80, 0x80), (0x00, 0x3D, 0xA6), (0x00, 0x12, 0xB0), (0x44, 0x00, 0x96), (0xA1, 0x00, 0x5E),
@@ -14,3 +16,42 @@ pub static SYSTEM_PALLETE: [(u8,u8,u8); 64] = [
    (0xFF, 0xEF, 0xA6), (0xFF, 0xF7, 0x9C), (0xD7, 0xE8, 0x95), (0xA6, 0xED, 0xAF), (0xA2, 0xF2, 0xDA),
    (0x99, 0xFF, 0xFC), (0xDD, 0xDD, 0xDD), (0x11, 0x11, 0x11), (0x11, 0x11, 0x11)
 ];
+
+// MaskRegister emphasis bits, shifted down to [0, 2]
+const EMPHASIZE_RED: u8 = 0b001;
+const EMPHASIZE_GREEN: u8 = 0b010;
+const EMPHASIZE_BLUE: u8 = 0b100;
+
+// Each emphasis bit darkens the other two channels to ~81.6%
+const EMPHASIS_ATTENUATION: u16 = 209; // / 256
+
+/**
+ * SYSTEM_PALLETE with each of the 8 combinations of colour emphasis applied.
+ * Indexed by emphasis << 6 | colour, where emphasis is MaskRegister::emphasis,
+ * in NTSC order.
+ */
+pub fn emphasized_palette() -> &'static [(u8, u8, u8); 512] {
+    static EMPHASIZED_PALETTE: OnceLock<[(u8, u8, u8); 512]> = OnceLock::new();
+    EMPHASIZED_PALETTE.get_or_init(|| {
+        let mut palette = [(0, 0, 0); 512];
+        for (index, color) in palette.iter_mut().enumerate() {
+            let emphasis = (index >> 6) as u8;
+            let (red, green, blue) = SYSTEM_PALLETE[index & 0x3F];
+            *color = (
+                attenuate(red, emphasis & !EMPHASIZE_RED),
+                attenuate(green, emphasis & !EMPHASIZE_GREEN),
+                attenuate(blue, emphasis & !EMPHASIZE_BLUE),
+            );
+        }
+        palette
+    })
+}
+
+/**
+ * Darkens a colour channel once for each emphasis bit set on the other channels
+ */
+fn attenuate(value: u8, other_emphasis: u8) -> u8 {
+    (0..other_emphasis.count_ones()).fold(value, |value, _| {
+        (value as u16 * EMPHASIS_ATTENUATION / 256) as u8
+    })
+}
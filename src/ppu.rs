@@ -1,17 +1,37 @@
 use crate::{
-    config::CHR_ROM_PAGE_SIZE,
-    rom::{Mirroring, Rom},
+    cartridge::{Cartridge, PPU_CARTRIDGE_END, PPU_CARTRIDGE_START},
+    frame::Frame,
+    palette,
+    region::Region,
+    rom::Mirroring,
 };
 use bitflags::bitflags;
 
-const CHR_ROM_START: u16 = 0x0000;
-const CHR_ROM_END: u16 = 0x1FFF;
 const VRAM_START: u16 = 0x2000;
-const VRAM_END: u16 = 0x2FFF;
+const VRAM_MIRRORS_END: u16 = 0x3EFF;
 const PALETTE_START: u16 = 0x3F00;
 const PALETTE_END: u16 = 0x3FFF;
 
+const PALETTE_SIZE: u16 = 0x20;
+// Palette entries are 6 bits: an index into palette::SYSTEM_PALLETE
+const PALETTE_VALUE_MASK: u8 = 0b0011_1111;
+
 const NAMETABLE_SIZE: u16 = 0x400;
+const ATTRIBUTE_TABLE_OFFSET: u16 = 0x3C0;
+
+const DOTS_PER_SCANLINE: u32 = 341;
+const VISIBLE_SCANLINES: u32 = 240;
+
+const OAM_SPRITE_SIZE: usize = 4;
+const OAM_SPRITE_COUNT: usize = 64;
+const MAX_SPRITES_PER_SCANLINE: usize = 8;
+
+// Bits 2-4 of a sprite's attribute byte don't exist, and read back as 0
+const OAM_ATTRIBUTE_MASK: u8 = 0xE3;
+const STATUS_FLAGS_MASK: u8 = 0xE0;
+
+// The I/O latch's bits decay about 600ms after they're last driven
+const IO_LATCH_DECAY_FRAMES: u32 = 36;
 
 const ADDRESS_REGISTER_MIRROR_DOWN_MASK: u16 = 0b0011_1111_1111_1111; // [0x4000, 0xFFFF] -> [0, 0x4000)
 const VRAM_MIRROR_DOWN_MASK: u16 = 0b0010_1111_1111_1111; // 0x3xxx -> 0x2xxx
@@ -39,7 +59,7 @@ bitflags! {
     pub struct ControlRegister: u8 {
        const GENERATE_NMI               	= 0b1000_0000;
        const MASTER_SLAVE_SELECT        	= 0b0100_0000;
-       const SPRITE_PATTERN_OFFSET          = 0b0010_0000;
+       const SPRITE_SIZE                    = 0b0010_0000;
        const BACKGROUND_PATTERN_OFFSET 	    = 0b0001_0000;
        const SPRITE_PATTERN_ADDRESS     	= 0b0000_1000;
        const VRAM_ADDRESS_INCREMENT     	= 0b0000_0100;
@@ -61,6 +81,26 @@ impl ControlRegister {
         }
     }
 
+    pub fn sprite_pattern_offset(&self) -> u16 {
+        if self.contains(Self::SPRITE_PATTERN_ADDRESS) {
+            0x1000
+        } else {
+            0
+        }
+    }
+
+    pub fn sprite_height(&self) -> u32 {
+        if self.contains(Self::SPRITE_SIZE) {
+            16
+        } else {
+            8
+        }
+    }
+
+    pub fn nametable(&self) -> u8 {
+        self.bits() & 0b11
+    }
+
     pub fn vram_address_increment_amount(&self) -> u8 {
         if self.contains(Self::VRAM_ADDRESS_INCREMENT) {
             32
@@ -102,6 +142,35 @@ impl MaskRegister {
     pub fn new() -> Self {
         MaskRegister::from_bits_retain(0)
     }
+
+    pub fn rendering_enabled(&self) -> bool {
+        self.intersects(Self::SHOW_BACKGROUND | Self::SHOW_SPRITES)
+    }
+
+    /**
+     * The emphasis bits, shifted down to [0, 2], in NTSC order: red, green,
+     * blue
+     */
+    pub fn emphasis(&self, region: Region) -> u8 {
+        let emphasis = self.bits() >> 5;
+        if region.swaps_red_green_emphasis() {
+            emphasis & 0b100 | (emphasis & 0b001) << 1 | (emphasis & 0b010) >> 1
+        } else {
+            emphasis
+        }
+    }
+
+    /**
+     * Mask applied to palette colours. Greyscale keeps only the colour's
+     * brightness, so every colour comes from the grey column $x0.
+     */
+    pub fn color_mask(&self) -> u8 {
+        if self.contains(Self::GREYSCALE) {
+            0x30
+        } else {
+            PALETTE_VALUE_MASK
+        }
+    }
 }
 
 bitflags! {
@@ -129,8 +198,8 @@ bitflags! {
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
     pub struct StatusRegister: u8 {
        const VBLANK_STARTED     = 0b1000_0000;
-       const B        	        = 0b0100_0000;
-       const C                  = 0b0010_0000;
+       const SPRITE_0_HIT       = 0b0100_0000;
+       const SPRITE_OVERFLOW    = 0b0010_0000;
        const D 	                = 0b0001_0000;
        const E     	            = 0b0000_1000;
        const F     	            = 0b0000_0100;
@@ -145,163 +214,387 @@ impl StatusRegister {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub struct ScrollRegister {
-    x_scroll: u8,
-    y_scroll: u8,
-    x_scroll_active: bool,
+// yyy NN YYYYY XXXXX
+// ||| || ||||| +++++- Coarse X scroll
+// ||| || +++++------- Coarse Y scroll
+// ||| ++------------- Nametable select
+// +++---------------- Fine Y scroll
+const COARSE_X_MASK: u16 = 0x001F;
+const COARSE_Y_MASK: u16 = 0x03E0;
+const NAMETABLE_X_MASK: u16 = 0x0400;
+const NAMETABLE_Y_MASK: u16 = 0x0800;
+const NAMETABLE_MASK: u16 = NAMETABLE_X_MASK | NAMETABLE_Y_MASK;
+const FINE_Y_MASK: u16 = 0x7000;
+const HORIZONTAL_MASK: u16 = COARSE_X_MASK | NAMETABLE_X_MASK;
+const VERTICAL_MASK: u16 = FINE_Y_MASK | NAMETABLE_Y_MASK | COARSE_Y_MASK;
+
+/**
+ * One of the PPU's internal 15-bit VRAM addresses ("loopy" registers): the current
+ * address v, or the temporary address t. While rendering, v holds the position
+ * of the tile being fetched.
+ * https://www.nesdev.org/wiki/PPU_scrolling
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VramAddress {
+    value: u16,
 }
 
-impl ScrollRegister {
+impl VramAddress {
     pub fn new() -> Self {
-        ScrollRegister {
-            x_scroll: 0,
-            y_scroll: 0,
-            x_scroll_active: true,
-        }
+        VramAddress { value: 0 }
     }
 
     /**
-     * If x_scroll_active, sets x_scroll to the given value.
-     * If not, sets y_scroll to the given value.
-     * Toggles x_scroll_active
+     * The address on the PPU's 14-bit address bus
      */
-    pub fn update(&mut self, value: u8) {
-        if self.x_scroll_active {
-            self.x_scroll = value;
-        } else {
-            self.y_scroll = value;
-        };
-        self.x_scroll_active = !self.x_scroll_active;
+    pub fn get(&self) -> u16 {
+        self.value & ADDRESS_REGISTER_MIRROR_DOWN_MASK
     }
 
-    pub fn reset_latch(&mut self) {
-        self.x_scroll_active = true;
+    pub fn coarse_x(&self) -> u16 {
+        self.value & COARSE_X_MASK
     }
-}
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub struct AddressRegister {
-    high_byte: u8,
-    low_byte: u8,
-    high_byte_active: bool,
-}
+    pub fn coarse_y(&self) -> u16 {
+        (self.value & COARSE_Y_MASK) >> 5
+    }
 
-impl AddressRegister {
-    pub fn new() -> Self {
-        AddressRegister {
-            high_byte: 0,
-            low_byte: 0,
-            high_byte_active: true,
-        }
+    pub fn fine_y(&self) -> u16 {
+        (self.value & FINE_Y_MASK) >> 12
+    }
+
+    pub fn set_nametable(&mut self, nametable: u8) {
+        self.value = (self.value & !NAMETABLE_MASK) | ((nametable as u16 & 0b11) << 10);
+    }
+
+    pub fn set_coarse_x(&mut self, coarse_x: u8) {
+        self.value = (self.value & !COARSE_X_MASK) | (coarse_x as u16 & 0b1_1111);
+    }
+
+    pub fn set_coarse_y(&mut self, coarse_y: u8) {
+        self.value = (self.value & !COARSE_Y_MASK) | ((coarse_y as u16 & 0b1_1111) << 5);
+    }
+
+    pub fn set_fine_y(&mut self, fine_y: u8) {
+        self.value = (self.value & !FINE_Y_MASK) | ((fine_y as u16 & 0b111) << 12);
     }
 
     /**
-     * If high_byte_active, sets high_byte to the given value.
-     * If not, sets low_byte to the given value.
-     * Mirrors down the u16 address containing the updated byte.
+     * Sets bits [8, 13] to the low 6 bits of the given value, and clears bit 14
      */
-    pub fn update(&mut self, value: u8) {
-        if self.high_byte_active {
-            self.high_byte = value;
+    pub fn set_high_byte(&mut self, value: u8) {
+        self.value = (self.value & 0x00FF) | ((value as u16 & 0b11_1111) << 8);
+    }
+
+    pub fn set_low_byte(&mut self, value: u8) {
+        self.value = (self.value & 0xFF00) | value as u16;
+    }
+
+    /**
+     * Increments the address by the given amount, as $2007 accesses do outside
+     * of rendering
+     */
+    pub fn increment(&mut self, amount: u8) {
+        self.value = (self.value + amount as u16) & 0x7FFF;
+    }
+
+    /**
+     * Moves to the next tile horizontally, switching to the horizontally
+     * adjacent nametable after the 32nd tile
+     */
+    pub fn increment_coarse_x(&mut self) {
+        if self.coarse_x() == 31 {
+            self.value &= !COARSE_X_MASK;
+            self.value ^= NAMETABLE_X_MASK;
         } else {
-            self.low_byte = value;
+            self.value += 1;
+        }
+    }
+
+    /**
+     * Moves to the next pixel row, switching to the vertically adjacent
+     * nametable after the 30th row of tiles. Coarse Y values of 30 and 31 (which
+     * point into the attribute table) wrap to 0 without switching nametable.
+     */
+    pub fn increment_y(&mut self) {
+        let fine_y = self.fine_y();
+        if fine_y < 7 {
+            self.set_fine_y(fine_y as u8 + 1);
+            return;
         }
-        self.high_byte_active = !self.high_byte_active;
 
-        self.mirror_down();
+        self.set_fine_y(0);
+        match self.coarse_y() {
+            29 => {
+                self.set_coarse_y(0);
+                self.value ^= NAMETABLE_Y_MASK;
+            }
+            31 => self.set_coarse_y(0),
+            coarse_y => self.set_coarse_y(coarse_y as u8 + 1),
+        }
     }
 
     /**
-     * Increments the address by the given amount. Wraps u16.
-     * Mirrors down the new value.
+     * Copies coarse X and the horizontal nametable bit from the given address
      */
-    pub fn increment(&mut self, amount: u8) {
-        let value = self.get().wrapping_add(amount as u16);
-        self.set(value);
+    pub fn copy_horizontal(&mut self, from: VramAddress) {
+        self.value = (self.value & !HORIZONTAL_MASK) | (from.value & HORIZONTAL_MASK);
+    }
 
-        self.mirror_down();
+    /**
+     * Copies fine Y, coarse Y and the vertical nametable bit from the given address
+     */
+    pub fn copy_vertical(&mut self, from: VramAddress) {
+        self.value = (self.value & !VERTICAL_MASK) | (from.value & VERTICAL_MASK);
     }
 
-    pub fn reset_latch(&mut self) {
-        self.high_byte_active = true;
+    /**
+     * Address of the nametable byte of the current tile
+     */
+    pub fn tile_address(&self) -> u16 {
+        VRAM_START | (self.value & (NAMETABLE_MASK | COARSE_Y_MASK | COARSE_X_MASK))
     }
 
-    fn get(&self) -> u16 {
-        u16::from_be_bytes([self.high_byte, self.low_byte])
+    /**
+     * Address of the attribute byte of the current tile. Each attribute byte
+     * covers a 4x4 tile area.
+     */
+    pub fn attribute_address(&self) -> u16 {
+        VRAM_START
+            + ATTRIBUTE_TABLE_OFFSET
+            + (self.value & NAMETABLE_MASK)
+            + (self.coarse_y() / 4) * 8
+            + self.coarse_x() / 4
     }
+}
 
-    fn set(&mut self, value: u16) {
-        let [high_byte, low_byte] = value.to_be_bytes();
-        self.high_byte = high_byte;
-        self.low_byte = low_byte;
+bitflags! {
+
+    // 7  bit  0
+    // ---- ----
+    // VHP. ..PP
+    // |||| ||||
+    // |||| ||++- Palette (4 to 7) of sprite
+    // |||+-++--- Unimplemented (read 0)
+    // ||+------- Priority (0: in front of background; 1: behind background)
+    // |+-------- Flip sprite horizontally
+    // +--------- Flip sprite vertically
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct SpriteAttributes: u8 {
+       const FLIP_VERTICAL              = 0b1000_0000;
+       const FLIP_HORIZONTAL            = 0b0100_0000;
+       const BEHIND_BACKGROUND          = 0b0010_0000;
+       const PALETTE_1                  = 0b0000_0010;
+       const PALETTE_2                  = 0b0000_0001;
+   }
+}
+
+impl SpriteAttributes {
+    pub fn palette(&self) -> u8 {
+        self.bits() & 0b11
     }
+}
 
+/**
+ * A sprite fetched for the next scanline. Horizontally flipped sprites have their
+ * patterns flipped when they're fetched.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+struct Sprite {
+    x: u8,
+    attributes: SpriteAttributes,
+    pattern_low: u8,
+    pattern_high: u8,
+}
+
+impl Sprite {
     /**
-     * Set the address to the lowest-mirror possibility.
+     * Pattern value of the sprite's pixel at x. 0 if it's transparent, or x is
+     * outside the sprite.
      */
-    fn mirror_down(&mut self) {
-        let mirror_down = self.get() & ADDRESS_REGISTER_MIRROR_DOWN_MASK;
-        self.set(mirror_down);
+    fn pattern(&self, x: u32) -> u8 {
+        match x.checked_sub(self.x as u32) {
+            Some(column @ 0..=7) => {
+                let bit = 7 - column;
+                ((self.pattern_high >> bit) & 1) << 1 | ((self.pattern_low >> bit) & 1)
+            }
+            _ => 0,
+        }
+    }
+}
+
+/**
+ * The data bus between the CPU and the PPU's registers. Reading a write-only
+ * register returns whatever is left on it, as do the unused bits of $2002 and
+ * palette reads. Each bit decays to 0 when it hasn't been driven for a while.
+ */
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct IoLatch {
+    value: u8,
+    // Frame each bit was last driven on
+    refreshed: [u32; 8],
+}
+
+impl IoLatch {
+    pub fn new() -> Self {
+        IoLatch {
+            value: 0,
+            refreshed: [0; 8],
+        }
+    }
+
+    /**
+     * Contents of the latch on the given frame, with decayed bits cleared
+     */
+    pub fn get(&self, frame: u32) -> u8 {
+        (0..8)
+            .filter(|&bit| frame.wrapping_sub(self.refreshed[bit]) < IO_LATCH_DECAY_FRAMES)
+            .fold(0, |value, bit| value | (self.value & (1 << bit)))
+    }
+
+    /**
+     * Drives the bits set in mask to those of value, leaving the others alone.
+     * Returns the new contents of the latch.
+     */
+    pub fn drive(&mut self, value: u8, mask: u8, frame: u32) -> u8 {
+        self.value = (self.get(frame) & !mask) | (value & mask);
+        for bit in 0..8 {
+            if mask & (1 << bit) != 0 {
+                self.refreshed[bit] = frame;
+            }
+        }
+        self.value
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub struct Ppu {
-    pub chr_rom: Vec<u8>,
     pub palette_table: [u8; 32],
-    pub vram: [u8; 2048],
+    /**
+     * The console's 2KB of nametable RAM, followed by the extra 2KB that
+     * four-screen cartridges provide
+     */
+    pub vram: [u8; 4096],
     pub oam: [u8; 256],
-    pub mirroring: Mirroring,
 
     pub control: ControlRegister,
     pub mask: MaskRegister,
     pub status: StatusRegister,
     pub oam_address: u8,
-    pub scroll: ScrollRegister,
-    pub vram_address: AddressRegister,
+    /**
+     * The current VRAM address, v
+     */
+    pub vram_address: VramAddress,
+    /**
+     * The temporary VRAM address, t. Holds the scroll position between frames,
+     * and the address being written to $2006.
+     */
+    pub temp_vram_address: VramAddress,
+    pub fine_x: u8,
+    /**
+     * The first/second write toggle shared by $2005 and $2006, w
+     */
+    pub write_latch: bool,
 
     pub nmi_interrupt: bool,
+    pub frame: Frame,
+    pub io_latch: IoLatch,
+    pub region: Region,
 
     data_buffer: u8,
+    /**
+     * CPU cycles' worth of dots not yet ticked, in units of 1 / the ratio's CPU
+     * cycles. Only PAL has a fractional ratio.
+     */
+    partial_dots: u32,
     cycles: u32,
     scanline: u32,
+    odd_frame: bool,
+    frame_count: u32,
+    /**
+     * Set by a $2002 read on the dot before vblank starts, which stops
+     * VBLANK_STARTED being set that frame
+     */
+    suppress_vblank: bool,
+
+    // Background tile fetched ahead of the shift registers
+    next_tile: u8,
+    next_attribute: u8,
+    next_pattern_low: u8,
+    next_pattern_high: u8,
+
+    // Background shift registers. Bit 15 - fine_x is the current pixel.
+    pattern_shift_low: u16,
+    pattern_shift_high: u16,
+    attribute_shift_low: u16,
+    attribute_shift_high: u16,
+
+    secondary_oam: [u8; 32],
+    sprite_count: usize,
+    sprite_0_on_line: bool,
+    sprites: [Sprite; MAX_SPRITES_PER_SCANLINE],
 }
 
 impl Ppu {
-    fn new_chr_rom_mirroring(chr_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+    pub fn new(region: Region) -> Self {
         Ppu {
-            chr_rom: chr_rom,
             palette_table: [0; 32],
-            vram: [0; 2048],
+            vram: [0; 4096],
             oam: [0; 256],
 
-            mirroring: mirroring,
-
             control: ControlRegister::new(),
             mask: MaskRegister::new(),
             status: StatusRegister::new(),
             oam_address: 0,
-            scroll: ScrollRegister::new(),
-            vram_address: AddressRegister::new(),
+            vram_address: VramAddress::new(),
+            temp_vram_address: VramAddress::new(),
+            fine_x: 0,
+            write_latch: false,
 
             nmi_interrupt: false,
+            frame: Frame::new(),
+            io_latch: IoLatch::new(),
+            region,
 
             data_buffer: 0,
+            partial_dots: 0,
             cycles: 0,
             scanline: 0,
+            odd_frame: false,
+            frame_count: 0,
+            suppress_vblank: false,
+
+            next_tile: 0,
+            next_attribute: 0,
+            next_pattern_low: 0,
+            next_pattern_high: 0,
+
+            pattern_shift_low: 0,
+            pattern_shift_high: 0,
+            attribute_shift_low: 0,
+            attribute_shift_high: 0,
+
+            secondary_oam: [0xFF; 32],
+            sprite_count: 0,
+            sprite_0_on_line: false,
+            sprites: [Sprite::default(); MAX_SPRITES_PER_SCANLINE],
         }
     }
 
-    pub fn new(rom: &Rom) -> Self {
-        Self::new_chr_rom_mirroring(rom.chr_rom.clone(), rom.mirroring)
+    /**
+     * Every write to a PPU register, including read-only $2002, drives the
+     * whole I/O latch
+     */
+    pub fn write_to_io_latch(&mut self, value: u8) {
+        self.io_latch.drive(value, 0xFF, self.frame_count);
     }
 
-    fn new_empty_rom() -> Self {
-        Self::new_chr_rom_mirroring(
-            Vec::from([0; CHR_ROM_PAGE_SIZE as usize]),
-            Mirroring::Horizontal,
-        )
+    /**
+     * Reads from a write-only register: bus::$2000, $2001, $2003, $2005 or $2006
+     */
+    pub fn read_from_io_latch(&self) -> u8 {
+        self.io_latch.get(self.frame_count)
     }
 
     /**
@@ -310,6 +603,8 @@ impl Ppu {
     pub fn write_to_control(&mut self, value: u8) {
         let nmi_before_write = self.control.contains(ControlRegister::GENERATE_NMI);
         self.control = ControlRegister::from_bits_retain(value);
+        self.temp_vram_address
+            .set_nametable(self.control.nametable());
         let nmi_after_write = self.control.contains(ControlRegister::GENERATE_NMI);
         if !nmi_before_write
             && nmi_after_write
@@ -317,6 +612,15 @@ impl Ppu {
         {
             self.nmi_interrupt = true;
         }
+
+        // Disabling NMI on the dot after vblank starts cancels its NMI
+        if nmi_before_write
+            && !nmi_after_write
+            && self.scanline == self.region.vblank_scanline()
+            && self.cycles == 2
+        {
+            self.nmi_interrupt = false;
+        }
     }
 
     /**
@@ -344,37 +648,50 @@ impl Ppu {
 
     /**
      * Writes to bus::$2005
+     * The first write sets the X scroll, and the second the Y scroll
      */
     pub fn write_to_scroll(&mut self, value: u8) {
-        self.scroll.update(value);
+        if !self.write_latch {
+            self.temp_vram_address.set_coarse_x(value >> 3);
+            self.fine_x = value & 0b111;
+        } else {
+            self.temp_vram_address.set_coarse_y(value >> 3);
+            self.temp_vram_address.set_fine_y(value & 0b111);
+        }
+        self.write_latch = !self.write_latch;
     }
 
     /**
      * Writes to bus::$2006
+     * The first write sets the high byte of t, and the second sets the low byte,
+     * then copies t to v
      */
     pub fn write_to_vram_address(&mut self, value: u8) {
-        self.vram_address.update(value);
+        if !self.write_latch {
+            self.temp_vram_address.set_high_byte(value);
+        } else {
+            self.temp_vram_address.set_low_byte(value);
+            self.vram_address = self.temp_vram_address;
+        }
+        self.write_latch = !self.write_latch;
     }
 
     /**
      * Writes to bus::$2007
      * Increments vram based on bit 2 of bus::$2000
      */
-    pub fn write_to_data(&mut self, value: u8) {
+    pub fn write_to_data(&mut self, cartridge: &mut dyn Cartridge, value: u8) {
         let address = self.vram_address.get();
 
         match address {
-            CHR_ROM_START..=CHR_ROM_END => {
-                panic!("Attempt to write to chr_rom at address: {:02X}", address)
-            }
-            VRAM_START..=VRAM_END => {
-                let mirror_down_vram_address = self.mirror_down_vram(address);
+            PPU_CARTRIDGE_START..=PPU_CARTRIDGE_END => cartridge.ppu_write(address, value),
+            VRAM_START..=VRAM_MIRRORS_END => {
+                let mirror_down_vram_address = self.mirror_down_vram(cartridge, address);
                 self.vram[mirror_down_vram_address as usize] = value;
             }
-            0x3000..=0x3EFF => {
-                unimplemented!("Attempt to read from unused PPU address: {:04X}", address)
+            PALETTE_START..=PALETTE_END => {
+                self.palette_table[mirror_down_palette(address)] = value & PALETTE_VALUE_MASK
             }
-            0x3F00..=0x3FFF => self.palette_table[(address - 0x3f00) as usize] = value,
             _ => panic!("Attempt to read from mirrored PPU address: {:04X}", address),
         }
         self.increment_address();
@@ -394,92 +711,558 @@ impl Ppu {
      * Reads data from bus::$2002
      */
     pub fn read_from_status(&mut self) -> u8 {
-        let value = self.status.bits();
+        // Races with VBLANK_STARTED being set at dot 1 of the vblank line.
+        // Reading on the dot before reads it clear, and stops it being set this
+        // frame. Reading on the same dot or the one after reads it set, but
+        // suppresses the NMI.
+        if self.scanline == self.region.vblank_scanline() {
+            match self.cycles {
+                1 => self.suppress_vblank = true,
+                2 | 3 => self.nmi_interrupt = false,
+                _ => {}
+            }
+        }
+
+        // Only the flags are driven. The low 5 bits are open bus.
+        let value = self
+            .io_latch
+            .drive(self.status.bits(), STATUS_FLAGS_MASK, self.frame_count);
         self.status.remove(StatusRegister::VBLANK_STARTED);
-        self.vram_address.reset_latch();
-        self.scroll.reset_latch();
+        self.write_latch = false;
         value
     }
 
     /**
      * Reads from bus::$2004
      */
-    pub fn read_from_oam_data(&self) -> u8 {
-        self.oam[self.oam_address as usize]
+    pub fn read_from_oam_data(&mut self) -> u8 {
+        let value = self.oam[self.oam_address as usize];
+        let value = if self.oam_address as usize % OAM_SPRITE_SIZE == 2 {
+            value & OAM_ATTRIBUTE_MASK
+        } else {
+            value
+        };
+        self.io_latch.drive(value, 0xFF, self.frame_count)
     }
 
     /**
      * Reads data from bus::$2007
      * Increments vram based on bit 2 of bus::$2000
      */
-    pub fn read_from_data(&mut self) -> u8 {
+    pub fn read_from_data(&mut self, cartridge: &mut dyn Cartridge) -> u8 {
         let address = self.vram_address.get();
         self.increment_address();
 
-        match address {
-            CHR_ROM_START..=CHR_ROM_END => {
+        let result = match address {
+            PPU_CARTRIDGE_START..=PPU_CARTRIDGE_END => {
                 let result = self.data_buffer;
-                self.data_buffer = self.chr_rom[address as usize];
+                self.data_buffer = cartridge.ppu_read(address);
                 result
             }
-            VRAM_START..=VRAM_END => {
+            VRAM_START..=VRAM_MIRRORS_END => {
                 let result = self.data_buffer;
-                let mirror_down_vram_address = self.mirror_down_vram(address);
+                let mirror_down_vram_address = self.mirror_down_vram(cartridge, address);
                 self.data_buffer = self.vram[mirror_down_vram_address as usize];
                 result
             }
-            0x3000..=0x3EFF => {
-                unimplemented!("Attempt to read from unused PPU address: {:04X}", address)
+            // Palette reads aren't buffered, but still fill the buffer with the
+            // nametable byte "underneath" the palette. The top 2 bits are open bus.
+            PALETTE_START..=PALETTE_END => {
+                self.data_buffer = self.read_vram(cartridge, address - 0x1000);
+                let value =
+                    self.palette_table[mirror_down_palette(address)] & self.mask.color_mask();
+                return self
+                    .io_latch
+                    .drive(value, PALETTE_VALUE_MASK, self.frame_count);
             }
-            0x3F00..=0x3FFF => self.palette_table[(address - 0x3f00) as usize],
             _ => panic!("Attempt to read from mirrored PPU address: {:04X}", address),
-        }
+        };
+        self.io_latch.drive(result, 0xFF, self.frame_count)
     }
 
-    pub fn tick(&mut self, cycles: u32) -> bool {
-        self.cycles += cycles;
-        if self.cycles >= 341 {
-            self.cycles -= 341;
-            self.scanline += 1;
+    /**
+     * Number of frames completed so far. Wraps around.
+     */
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    pub fn tick(&mut self, cartridge: &mut dyn Cartridge, cycles: u32) -> bool {
+        let mut frame_complete = false;
+        for _ in 0..cycles {
+            frame_complete |= self.tick_dot(cartridge);
         }
+        frame_complete
+    }
 
-        if self.scanline == 241 {
-            self.status.insert(StatusRegister::VBLANK_STARTED);
-            if self.control.contains(ControlRegister::GENERATE_NMI) {
-                self.nmi_interrupt = true;
+    /**
+     * Advances the PPU by the dots that happen in the given number of CPU cycles.
+     * Returns true if a frame was completed
+     */
+    pub fn tick_cpu_cycles(&mut self, cartridge: &mut dyn Cartridge, cycles: u32) -> bool {
+        let (dots, cpu_cycles) = self.region.ppu_clock_ratio();
+        let scaled_dots = cycles * dots + self.partial_dots;
+        self.partial_dots = scaled_dots % cpu_cycles;
+        self.tick(cartridge, scaled_dots / cpu_cycles)
+    }
+
+    /**
+     * Advances the PPU by one dot.
+     * Returns true when the frame is complete
+     */
+    fn tick_dot(&mut self, cartridge: &mut dyn Cartridge) -> bool {
+        cartridge.tick_ppu_dot();
+        let pre_render_scanline = self.region.pre_render_scanline();
+        if self.cycles == 1 {
+            if self.scanline == self.region.vblank_scanline() {
+                self.start_vblank();
+            } else if self.scanline == pre_render_scanline {
+                self.status.remove(
+                    StatusRegister::VBLANK_STARTED
+                        | StatusRegister::SPRITE_0_HIT
+                        | StatusRegister::SPRITE_OVERFLOW,
+                );
             }
         }
 
-        if self.scanline >= 262 {
+        self.render_dot(cartridge);
+
+        self.cycles += 1;
+        // NTSC odd frames skip the last dot of the pre-render line, if rendering
+        // is enabled
+        if self.scanline == pre_render_scanline
+            && self.cycles == DOTS_PER_SCANLINE - 1
+            && self.odd_frame
+            && self.mask.rendering_enabled()
+            && self.region.skips_odd_frame_dot()
+        {
+            self.cycles += 1;
+        }
+
+        if self.cycles >= DOTS_PER_SCANLINE {
+            self.cycles -= DOTS_PER_SCANLINE;
+            self.scanline += 1;
+        }
+
+        if self.scanline >= self.region.scanlines_per_frame() {
             self.scanline = 0;
-            self.nmi_interrupt = false;
-            self.status.remove(StatusRegister::VBLANK_STARTED);
+            self.odd_frame = !self.odd_frame;
+            self.frame_count = self.frame_count.wrapping_add(1);
             return true;
         }
 
         false
     }
 
+    /**
+     * Sets VBLANK_STARTED, and raises an NMI if it's enabled
+     */
+    fn start_vblank(&mut self) {
+        if self.suppress_vblank {
+            self.suppress_vblank = false;
+            return;
+        }
+
+        self.status.insert(StatusRegister::VBLANK_STARTED);
+        if self.control.contains(ControlRegister::GENERATE_NMI) {
+            self.nmi_interrupt = true;
+        }
+    }
+
+    /**
+     * Does the rendering work for the current dot: background and sprite
+     * fetches for upcoming pixels, then outputting this dot's pixel to the frame.
+     * Mappers like MMC3 watch the pattern table fetches to count scanlines.
+     */
+    fn render_dot(&mut self, cartridge: &mut dyn Cartridge) {
+        let dot = self.cycles;
+
+        if self.is_rendering() {
+            self.fetch_background(cartridge, dot);
+            self.fetch_sprites(cartridge, dot);
+        }
+
+        if self.scanline < VISIBLE_SCANLINES && (1..=256).contains(&dot) {
+            self.output_pixel(dot - 1);
+        }
+    }
+
+    /**
+     * Whether the PPU is fetching tiles and sprites: rendering is enabled, and
+     * it's on a visible scanline or the pre-render scanline
+     */
+    fn is_rendering(&self) -> bool {
+        let render_line =
+            self.scanline < VISIBLE_SCANLINES || self.scanline == self.region.pre_render_scanline();
+        self.mask.rendering_enabled() && render_line
+    }
+
+    /**
+     * Background tiles are fetched at dots [1, 256], two tiles ahead of the
+     * pixel output, with the first two tiles of the next line fetched at [321, 336].
+     * Each 8-dot fetch reads the nametable byte on its 1st dot, the attribute byte
+     * on its 3rd, the low pattern plane on its 5th, and the high plane on its 7th,
+     * then moves v to the next tile on its 8th.
+     * The shift registers are reloaded with the fetched tile every 8 dots.
+     *
+     * At dot 256 v moves to the next row, and at dot 257 its horizontal position
+     * is reset from t. On the pre-render line, its vertical position is reset from
+     * t at dots [280, 304].
+     */
+    fn fetch_background(&mut self, cartridge: &mut dyn Cartridge, dot: u32) {
+        if matches!(dot, 2..=257 | 321..=337) {
+            self.shift_background();
+            if dot % 8 == 1 {
+                self.load_background();
+            }
+        }
+
+        if matches!(dot, 1..=256 | 321..=336) {
+            match dot % 8 {
+                1 => {
+                    let tile_address = self.vram_address.tile_address();
+                    self.next_tile = self.read_vram(cartridge, tile_address);
+                }
+                3 => {
+                    // Each 2 bits of an attribute byte hold the palette of 2x2 tiles
+                    let attribute_address = self.vram_address.attribute_address();
+                    let shift = ((self.vram_address.coarse_y() & 0b10) << 1)
+                        | (self.vram_address.coarse_x() & 0b10);
+                    self.next_attribute =
+                        (self.read_vram(cartridge, attribute_address) >> shift) & 0b11;
+                }
+                5 => self.next_pattern_low = cartridge.ppu_read(self.background_pattern_address()),
+                7 => {
+                    self.next_pattern_high =
+                        cartridge.ppu_read(self.background_pattern_address() + 8)
+                }
+                0 => self.vram_address.increment_coarse_x(),
+                _ => {}
+            }
+        }
+
+        match dot {
+            256 => self.vram_address.increment_y(),
+            257 => self.vram_address.copy_horizontal(self.temp_vram_address),
+            280..=304 if self.scanline == self.region.pre_render_scanline() => {
+                self.vram_address.copy_vertical(self.temp_vram_address)
+            }
+            _ => {}
+        }
+    }
+
+    fn background_pattern_address(&self) -> u16 {
+        self.control.background_pattern_offset()
+            + self.next_tile as u16 * 16
+            + self.vram_address.fine_y()
+    }
+
+    fn shift_background(&mut self) {
+        self.pattern_shift_low <<= 1;
+        self.pattern_shift_high <<= 1;
+        self.attribute_shift_low <<= 1;
+        self.attribute_shift_high <<= 1;
+    }
+
+    /**
+     * Loads the fetched tile into the low byte of the shift registers
+     */
+    fn load_background(&mut self) {
+        let attribute_bits = |bit: u8| {
+            if self.next_attribute & bit != 0 {
+                0xFF
+            } else {
+                0
+            }
+        };
+
+        self.pattern_shift_low = (self.pattern_shift_low & 0xFF00) | self.next_pattern_low as u16;
+        self.pattern_shift_high =
+            (self.pattern_shift_high & 0xFF00) | self.next_pattern_high as u16;
+        self.attribute_shift_low = (self.attribute_shift_low & 0xFF00) | attribute_bits(0b01);
+        self.attribute_shift_high = (self.attribute_shift_high & 0xFF00) | attribute_bits(0b10);
+    }
+
+    /**
+     * Sprites for the next line are evaluated at dot 257, then their patterns are
+     * fetched at [257, 320]. Each 8-dot fetch reads the low plane on its 5th dot,
+     * and the high plane on its 7th.
+     */
+    fn fetch_sprites(&mut self, cartridge: &mut dyn Cartridge, dot: u32) {
+        if dot == 257 {
+            self.evaluate_sprites();
+        }
+        if !(257..=320).contains(&dot) {
+            return;
+        }
+
+        let slot = ((dot - 257) / 8) as usize;
+        let plane_offset = match dot % 8 {
+            5 => 0,
+            7 => 8,
+            _ => return,
+        };
+        let mut pattern = cartridge.ppu_read(self.sprite_pattern_address(slot) + plane_offset);
+
+        if slot < self.sprite_count {
+            let attributes =
+                SpriteAttributes::from_bits_retain(self.secondary_oam[slot * OAM_SPRITE_SIZE + 2]);
+            if attributes.contains(SpriteAttributes::FLIP_HORIZONTAL) {
+                pattern = pattern.reverse_bits();
+            }
+
+            let sprite = &mut self.sprites[slot];
+            if plane_offset == 0 {
+                sprite.x = self.secondary_oam[slot * OAM_SPRITE_SIZE + 3];
+                sprite.attributes = attributes;
+                sprite.pattern_low = pattern;
+            } else {
+                sprite.pattern_high = pattern;
+            }
+        }
+    }
+
+    /**
+     * Copies the first 8 sprites in OAM that are on the current scanline to
+     * secondary OAM. They will be drawn on the next scanline.
+     *
+     * If there are more, sets SPRITE_OVERFLOW. Due to a hardware bug, the search
+     * for a 9th sprite also increments the byte it checks within each sprite,
+     * reading tiles, attributes and x positions as y positions. This causes
+     * both false positives and false negatives.
+     */
+    fn evaluate_sprites(&mut self) {
+        self.secondary_oam = [0xFF; 32];
+        self.sprite_count = 0;
+        self.sprite_0_on_line = false;
+        if self.scanline >= VISIBLE_SCANLINES {
+            return;
+        }
+
+        let scanline = self.scanline;
+        let sprite_height = self.control.sprite_height();
+        let in_range = |sprite_y: u8| scanline.wrapping_sub(sprite_y as u32) < sprite_height;
+
+        let mut n = 0;
+        while n < OAM_SPRITE_COUNT && self.sprite_count < MAX_SPRITES_PER_SCANLINE {
+            let sprite = &self.oam[(n * OAM_SPRITE_SIZE)..((n + 1) * OAM_SPRITE_SIZE)];
+            if in_range(sprite[0]) {
+                let start = self.sprite_count * OAM_SPRITE_SIZE;
+                self.secondary_oam[start..(start + OAM_SPRITE_SIZE)].copy_from_slice(sprite);
+                self.sprite_count += 1;
+                self.sprite_0_on_line |= n == 0;
+            }
+            n += 1;
+        }
+
+        let mut m = 0;
+        while n < OAM_SPRITE_COUNT {
+            if in_range(self.oam[n * OAM_SPRITE_SIZE + m]) {
+                self.status.insert(StatusRegister::SPRITE_OVERFLOW);
+                break;
+            }
+            n += 1;
+            m = (m + 1) % OAM_SPRITE_SIZE;
+        }
+    }
+
+    /**
+     * Pattern address of the sprite in the given secondary OAM slot, on the next
+     * scanline. Empty slots fetch tile $FF.
+     *
+     * 8x16 sprites ignore the sprite pattern table select. Instead bit 0 of the
+     * tile number selects the pattern table, and the top half is the even tile
+     * below it, with the bottom half the tile after.
+     */
+    fn sprite_pattern_address(&self, slot: usize) -> u16 {
+        if slot >= self.sprite_count {
+            return if self.control.contains(ControlRegister::SPRITE_SIZE) {
+                0x1000 + 0xFE * 16
+            } else {
+                self.control.sprite_pattern_offset() + 0xFF * 16
+            };
+        }
+
+        let sprite_y = self.secondary_oam[slot * OAM_SPRITE_SIZE];
+        let tile = self.secondary_oam[slot * OAM_SPRITE_SIZE + 1];
+        let attributes =
+            SpriteAttributes::from_bits_retain(self.secondary_oam[slot * OAM_SPRITE_SIZE + 2]);
+
+        let sprite_height = self.control.sprite_height();
+        let mut row = self.scanline - sprite_y as u32;
+        if attributes.contains(SpriteAttributes::FLIP_VERTICAL) {
+            row = sprite_height - 1 - row;
+        }
+
+        if sprite_height == 16 {
+            let pattern_table = (tile & 1) as u16 * 0x1000;
+            let tile = (tile & 0xFE) as u16 + (row / 8) as u16;
+            pattern_table + tile * 16 + (row % 8) as u16
+        } else {
+            self.control.sprite_pattern_offset() + tile as u16 * 16 + row as u16
+        }
+    }
+
+    /**
+     * Writes the pixel at x on the current scanline to the frame.
+     * Opaque sprite pixels are drawn over the background, unless the sprite is
+     * behind the background and the background pixel is opaque.
+     */
+    fn output_pixel(&mut self, x: u32) {
+        let background = self.background_pixel(x);
+        self.check_sprite_0_hit(x, background);
+
+        let pixel = match self.sprite_pixel(x) {
+            // A lower-index sprite behind the background still hides sprites in front of it
+            Some((sprite, attributes))
+                if background == 0 || !attributes.contains(SpriteAttributes::BEHIND_BACKGROUND) =>
+            {
+                sprite
+            }
+            _ => background,
+        };
+
+        // With rendering disabled, the backdrop is the palette entry v points to, if any
+        let vram_address = self.vram_address.get();
+        let pixel = if !self.mask.rendering_enabled() && vram_address >= PALETTE_START {
+            (vram_address - PALETTE_START) as u8
+        } else {
+            pixel
+        };
+
+        self.frame
+            .set_pixel(x as usize, self.scanline as usize, self.pixel_color(pixel));
+    }
+
+    /**
+     * Sets SPRITE_0_HIT when an opaque pixel of sprite 0 overlaps an opaque
+     * background pixel, regardless of priority. There's no hit at x = 255, or in
+     * the left 8 pixels when either layer is hidden there.
+     */
+    fn check_sprite_0_hit(&mut self, x: u32, background: u8) {
+        let left_clipped = x < 8
+            && !self
+                .mask
+                .contains(MaskRegister::LEFTMOST_8_BACKGROUND | MaskRegister::LEFTMOST_8_SPRITES);
+        if !self.sprite_0_on_line
+            || !self.mask.contains(MaskRegister::SHOW_SPRITES)
+            || background == 0
+            || x == 255
+            || left_clipped
+        {
+            return;
+        }
+
+        if self.sprites[0].pattern(x) != 0 {
+            self.status.insert(StatusRegister::SPRITE_0_HIT);
+        }
+    }
+
+    /**
+     * Palette RAM index of the current background pixel at x, in [$00, $0F].
+     * Transparent pixels are 0.
+     */
+    fn background_pixel(&self, x: u32) -> u8 {
+        let left_clipped = x < 8 && !self.mask.contains(MaskRegister::LEFTMOST_8_BACKGROUND);
+        if !self.mask.contains(MaskRegister::SHOW_BACKGROUND) || left_clipped {
+            return 0;
+        }
+
+        let bit = 15 - self.fine_x;
+        let pattern = (((self.pattern_shift_high >> bit) & 1) << 1
+            | ((self.pattern_shift_low >> bit) & 1)) as u8;
+        let palette = (((self.attribute_shift_high >> bit) & 1) << 1
+            | ((self.attribute_shift_low >> bit) & 1)) as u8;
+        if pattern == 0 {
+            0
+        } else {
+            palette << 2 | pattern
+        }
+    }
+
+    /**
+     * Palette RAM index of the first opaque sprite pixel at x, in [$10, $1F],
+     * and its sprite's attributes
+     */
+    fn sprite_pixel(&self, x: u32) -> Option<(u8, SpriteAttributes)> {
+        let left_clipped = x < 8 && !self.mask.contains(MaskRegister::LEFTMOST_8_SPRITES);
+        if !self.mask.contains(MaskRegister::SHOW_SPRITES) || left_clipped {
+            return None;
+        }
+
+        self.sprites[..self.sprite_count].iter().find_map(|sprite| {
+            let pattern = sprite.pattern(x);
+            let pixel = 0x10 | sprite.attributes.palette() << 2 | pattern;
+            (pattern != 0).then_some((pixel, sprite.attributes))
+        })
+    }
+
+    /**
+     * RGB colour of the given palette RAM index, with greyscale and colour
+     * emphasis applied
+     */
+    fn pixel_color(&self, pixel: u8) -> (u8, u8, u8) {
+        let color = self.palette_table[mirror_down_palette(PALETTE_START + pixel as u16)]
+            & self.mask.color_mask();
+        let emphasis = self.mask.emphasis(self.region);
+        palette::emphasized_palette()[(emphasis as usize) << 6 | color as usize]
+    }
+
+    fn read_vram(&self, cartridge: &dyn Cartridge, address: u16) -> u8 {
+        self.vram[self.mirror_down_vram(cartridge, address) as usize]
+    }
+
+    /**
+     * Increments v after a $2007 access. While rendering, this instead glitchily
+     * moves v to the next tile and the next row at once.
+     */
     fn increment_address(&mut self) {
-        self.vram_address
-            .increment(self.control.vram_address_increment_amount());
+        if self.is_rendering() {
+            self.vram_address.increment_coarse_x();
+            self.vram_address.increment_y();
+        } else {
+            self.vram_address
+                .increment(self.control.vram_address_increment_amount());
+        }
     }
 
-    fn mirror_down_vram(&self, address: u16) -> u16 {
+    /**
+     * Index into vram of the given address in [$2000, $3EFF].
+     * [$3000, $3EFF] mirrors [$2000, $2EFF].
+     */
+    fn mirror_down_vram(&self, cartridge: &dyn Cartridge, address: u16) -> u16 {
+        let address = address & VRAM_MIRROR_DOWN_MASK;
         let vram_index = address - VRAM_START;
         let nametable_index = vram_index / NAMETABLE_SIZE;
         let nametable_offset = address % NAMETABLE_SIZE;
-        let nametable_start = match (self.mirroring, nametable_index) {
+        let nametable_start = match (cartridge.mirroring(), nametable_index) {
             (Mirroring::Horizontal, 0 | 1) => 0,
             (Mirroring::Horizontal, 2 | 3) => NAMETABLE_SIZE,
             (Mirroring::Vertical, 0 | 2) => 0,
             (Mirroring::Vertical, 1 | 3) => NAMETABLE_SIZE,
+            (Mirroring::SingleScreenLower, _) => 0,
+            (Mirroring::SingleScreenUpper, _) => NAMETABLE_SIZE,
+            (Mirroring::FourScreen, 0..=3) => nametable_index * NAMETABLE_SIZE,
             _ => panic!("Nametable index >3: {:}", nametable_index),
         };
         nametable_start + nametable_offset
     }
 }
 
+/**
+ * Index into palette RAM of the given address in [$3F00, $3FFF].
+ * Palette RAM is mirrored every 32 bytes, and the sprite palettes' transparent
+ * entries, $3F10/$3F14/$3F18/$3F1C, are mirrors of $3F00/$3F04/$3F08/$3F0C.
+ */
+fn mirror_down_palette(address: u16) -> usize {
+    let index = (address - PALETTE_START) % PALETTE_SIZE;
+    if index >= 0x10 && index & 0b11 == 0 {
+        (index - 0x10) as usize
+    } else {
+        index as usize
+    }
+}
+
 pub fn poll_nmi_status(ppu: &mut Ppu) -> bool {
     if ppu.nmi_interrupt {
         ppu.nmi_interrupt = false;
@@ -488,40 +1271,553 @@ pub fn poll_nmi_status(ppu: &mut Ppu) -> bool {
         false
     }
 }
+#[cfg(test)]
 pub mod test {
     use crate::{
-        ppu::{Ppu, StatusRegister},
-        rom::Mirroring,
+        cartridge::{self, Cartridge},
+        palette,
+        ppu::{
+            poll_nmi_status, ControlRegister, IoLatch, MaskRegister, Ppu, SpriteAttributes,
+            StatusRegister, VramAddress, DOTS_PER_SCANLINE, IO_LATCH_DECAY_FRAMES,
+        },
+        region::Region,
+        rom::{Mirroring, Rom},
     };
 
+    // The tests use NTSC timing, unless they're testing other regions
+    const SCANLINES_PER_FRAME: u32 = Region::Ntsc.scanlines_per_frame();
+    const PRE_RENDER_SCANLINE: u32 = Region::Ntsc.pre_render_scanline();
+    const VBLANK_SCANLINE: u32 = Region::Ntsc.vblank_scanline();
+
+    fn new_empty_cartridge() -> Box<dyn Cartridge> {
+        cartridge::new(Rom::new_empty())
+    }
+
+    /**
+     * Writes a tile to pattern memory, with each row's low and high plane
+     */
+    fn write_tile(cartridge: &mut dyn Cartridge, address: u16, rows: [(u8, u8); 8]) {
+        for (row, (low, high)) in rows.into_iter().enumerate() {
+            cartridge.ppu_write(address + row as u16, low);
+            cartridge.ppu_write(address + row as u16 + 8, high);
+        }
+    }
+
+    /**
+     * A CHR-RAM cartridge where tile 1 is solid, with pattern value 1
+     */
+    fn new_solid_tile_cartridge() -> Box<dyn Cartridge> {
+        let mut cartridge = cartridge::new(Rom {
+            chr_rom: vec![],
+            chr_ram_size: 0x2000,
+            ..Rom::new_empty()
+        });
+        write_tile(cartridge.as_mut(), 0x10, [(0xFF, 0); 8]);
+        cartridge
+    }
+
+    /**
+     * Sets background palette 0 and sprite palette 4 to the same colours, with
+     * pattern values 1, 2 and 3 drawn as $23, $27 and $30 over a $01 backdrop
+     */
+    fn set_test_palette(ppu: &mut Ppu) {
+        ppu.palette_table[0x00..0x04].copy_from_slice(&[0x01, 0x23, 0x27, 0x30]);
+        ppu.palette_table[0x11..0x14].copy_from_slice(&[0x23, 0x27, 0x30]);
+    }
+
+    /**
+     * Ticks through two frames, so the first has had its pre-render line
+     */
+    fn render_frame(ppu: &mut Ppu, cartridge: &mut dyn Cartridge) {
+        ppu.tick(cartridge, DOTS_PER_SCANLINE * SCANLINES_PER_FRAME * 2);
+    }
+
     #[test]
 
     fn test_ppu_vram_writes() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
         ppu.write_to_vram_address(0x23);
         ppu.write_to_vram_address(0x05);
-        ppu.write_to_data(0x66);
+        ppu.write_to_data(cartridge.as_mut(), 0x66);
 
         assert_eq!(ppu.vram[0x0305], 0x66);
     }
 
+    #[test]
+    fn test_ppu_chr_ram_writes() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = cartridge::new(Rom {
+            chr_rom: vec![],
+            chr_ram_size: 0x2000,
+            ..Rom::new_empty()
+        });
+        ppu.write_to_vram_address(0x10);
+        ppu.write_to_vram_address(0x05);
+        ppu.write_to_data(cartridge.as_mut(), 0x66);
+
+        ppu.write_to_vram_address(0x10);
+        ppu.write_to_vram_address(0x05);
+        ppu.read_from_data(cartridge.as_mut()); //load_into_buffer
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x66);
+    }
+
+    #[test]
+    fn test_background_renders_to_frame() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        set_test_palette(&mut ppu);
+        let mut cartridge = new_solid_tile_cartridge();
+        ppu.vram[0x0021] = 1; // tile (1, 1)
+        ppu.write_to_mask(MaskRegister::SHOW_BACKGROUND.bits());
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        assert_eq!(ppu.frame.get_pixel(7, 7), palette::SYSTEM_PALLETE[0x01]);
+        assert_eq!(ppu.frame.get_pixel(8, 8), palette::SYSTEM_PALLETE[0x23]);
+        assert_eq!(ppu.frame.get_pixel(15, 15), palette::SYSTEM_PALLETE[0x23]);
+        assert_eq!(ppu.frame.get_pixel(16, 8), palette::SYSTEM_PALLETE[0x01]);
+    }
+
+    #[test]
+    fn test_sprite_renders_to_frame() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        set_test_palette(&mut ppu);
+        let mut cartridge = new_solid_tile_cartridge();
+        ppu.oam[0..4].copy_from_slice(&[9, 1, 0, 20]); // y, tile, attributes, x
+        ppu.write_to_mask(MaskRegister::SHOW_SPRITES.bits());
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        assert_eq!(ppu.frame.get_pixel(20, 9), palette::SYSTEM_PALLETE[0x01]);
+        assert_eq!(ppu.frame.get_pixel(20, 10), palette::SYSTEM_PALLETE[0x23]);
+        assert_eq!(ppu.frame.get_pixel(27, 17), palette::SYSTEM_PALLETE[0x23]);
+        assert_eq!(ppu.frame.get_pixel(28, 10), palette::SYSTEM_PALLETE[0x01]);
+        assert_eq!(ppu.frame.get_pixel(20, 18), palette::SYSTEM_PALLETE[0x01]);
+    }
+
+    #[test]
+    fn test_sprite_flipping() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        set_test_palette(&mut ppu);
+        let mut cartridge = new_solid_tile_cartridge();
+        // Tile 2 has a single pixel in its top-left corner
+        let mut rows = [(0, 0); 8];
+        rows[0] = (0b1000_0000, 0);
+        write_tile(cartridge.as_mut(), 0x20, rows);
+
+        let flip_vertical = SpriteAttributes::FLIP_VERTICAL.bits();
+        let flip_horizontal = SpriteAttributes::FLIP_HORIZONTAL.bits();
+        ppu.oam[0..16].copy_from_slice(
+            &[
+                [9, 2, 0, 0],
+                [9, 2, flip_horizontal, 16],
+                [29, 2, flip_vertical, 0],
+                [29, 2, flip_vertical | flip_horizontal, 16],
+            ]
+            .concat(),
+        );
+        ppu.write_to_mask((MaskRegister::SHOW_SPRITES | MaskRegister::LEFTMOST_8_SPRITES).bits());
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        let sprite = palette::SYSTEM_PALLETE[0x23];
+        assert_eq!(ppu.frame.get_pixel(0, 10), sprite);
+        assert_eq!(ppu.frame.get_pixel(23, 10), sprite);
+        assert_eq!(ppu.frame.get_pixel(0, 37), sprite);
+        assert_eq!(ppu.frame.get_pixel(23, 37), sprite);
+
+        assert_eq!(ppu.frame.get_pixel(7, 10), palette::SYSTEM_PALLETE[0x01]);
+        assert_eq!(ppu.frame.get_pixel(16, 10), palette::SYSTEM_PALLETE[0x01]);
+        assert_eq!(ppu.frame.get_pixel(0, 30), palette::SYSTEM_PALLETE[0x01]);
+    }
+
+    #[test]
+    fn test_sprite_priority() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        set_test_palette(&mut ppu);
+        let mut cartridge = new_solid_tile_cartridge();
+        // Tile 2 is solid, with pattern value 2
+        write_tile(cartridge.as_mut(), 0x20, [(0, 0xFF); 8]);
+        ppu.vram[0x0021] = 1; // tile (1, 1)
+        ppu.vram[0x0041] = 1; // tile (1, 2)
+
+        let behind_background = SpriteAttributes::BEHIND_BACKGROUND.bits();
+        ppu.oam[0..12].copy_from_slice(
+            &[
+                [7, 2, 0, 4],
+                [15, 2, behind_background, 4],
+                // Hidden where the behind-background sprite overlaps the background
+                [15, 2, 0, 4],
+            ]
+            .concat(),
+        );
+        ppu.write_to_mask(
+            (MaskRegister::SHOW_BACKGROUND
+                | MaskRegister::SHOW_SPRITES
+                | MaskRegister::LEFTMOST_8_BACKGROUND
+                | MaskRegister::LEFTMOST_8_SPRITES)
+                .bits(),
+        );
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        let background = palette::SYSTEM_PALLETE[0x23];
+        let sprite = palette::SYSTEM_PALLETE[0x27];
+        assert_eq!(ppu.frame.get_pixel(4, 8), sprite);
+        assert_eq!(ppu.frame.get_pixel(8, 8), sprite);
+        assert_eq!(ppu.frame.get_pixel(4, 16), sprite);
+        assert_eq!(ppu.frame.get_pixel(8, 16), background);
+        assert_eq!(ppu.frame.get_pixel(12, 16), background);
+    }
+
+    #[test]
+    fn test_8x16_sprites() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        set_test_palette(&mut ppu);
+        let mut cartridge = new_solid_tile_cartridge();
+        // Tiles $02 and $03 in the right pattern table, with pattern values 1 and 2
+        write_tile(cartridge.as_mut(), 0x1020, [(0xFF, 0); 8]);
+        write_tile(cartridge.as_mut(), 0x1030, [(0, 0xFF); 8]);
+
+        ppu.oam[0..4].copy_from_slice(&[9, 0x03, 0, 20]);
+        ppu.write_to_control(ControlRegister::SPRITE_SIZE.bits());
+        ppu.write_to_mask(MaskRegister::SHOW_SPRITES.bits());
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        assert_eq!(ppu.frame.get_pixel(20, 10), palette::SYSTEM_PALLETE[0x23]);
+        assert_eq!(ppu.frame.get_pixel(20, 17), palette::SYSTEM_PALLETE[0x23]);
+        assert_eq!(ppu.frame.get_pixel(20, 18), palette::SYSTEM_PALLETE[0x27]);
+        assert_eq!(ppu.frame.get_pixel(20, 25), palette::SYSTEM_PALLETE[0x27]);
+        assert_eq!(ppu.frame.get_pixel(20, 26), palette::SYSTEM_PALLETE[0x01]);
+    }
+
+    #[test]
+    fn test_sprites_per_scanline_limit() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        set_test_palette(&mut ppu);
+        let mut cartridge = new_solid_tile_cartridge();
+        for sprite in 0..9 {
+            let start = sprite * 4;
+            ppu.oam[start..(start + 4)].copy_from_slice(&[9, 1, 0, sprite as u8 * 8]);
+        }
+        ppu.write_to_mask(MaskRegister::SHOW_SPRITES.bits());
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        assert_eq!(ppu.frame.get_pixel(63, 10), palette::SYSTEM_PALLETE[0x23]);
+        assert_eq!(ppu.frame.get_pixel(64, 10), palette::SYSTEM_PALLETE[0x01]);
+    }
+
+    #[test]
+    fn test_sprite_0_hit() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_solid_tile_cartridge();
+        ppu.vram[0x0021] = 1; // tile (1, 1)
+        ppu.oam[0..4].copy_from_slice(&[7, 1, 0, 12]);
+        ppu.write_to_mask((MaskRegister::SHOW_BACKGROUND | MaskRegister::SHOW_SPRITES).bits());
+
+        // Up to x = 11 on line 8
+        ppu.tick(cartridge.as_mut(), DOTS_PER_SCANLINE * 8 + 13);
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_0_HIT));
+
+        ppu.tick(cartridge.as_mut(), 1);
+        assert!(ppu.status.contains(StatusRegister::SPRITE_0_HIT));
+
+        // Cleared at dot 1 of the pre-render line
+        ppu.tick(
+            cartridge.as_mut(),
+            DOTS_PER_SCANLINE * (PRE_RENDER_SCANLINE - 8),
+        );
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_0_HIT));
+    }
+
+    #[test]
+    fn test_sprite_overflow() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.oam = [0xFF; 256];
+        for sprite in 0..9 {
+            ppu.oam[sprite * 4] = 9;
+        }
+        ppu.write_to_mask(MaskRegister::SHOW_SPRITES.bits());
+
+        ppu.tick(cartridge.as_mut(), DOTS_PER_SCANLINE * 10);
+        assert!(ppu.status.contains(StatusRegister::SPRITE_OVERFLOW));
+
+        ppu.tick(
+            cartridge.as_mut(),
+            DOTS_PER_SCANLINE * (PRE_RENDER_SCANLINE - 9),
+        );
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_OVERFLOW));
+    }
+
+    #[test]
+    fn test_sprite_overflow_diagonal_bug() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.oam = [0xFF; 256];
+        for sprite in 0..8 {
+            ppu.oam[sprite * 4] = 9;
+        }
+        ppu.write_to_mask(MaskRegister::SHOW_SPRITES.bits());
+
+        // False negative: sprite 9 is on the line, but its tile is checked instead of its y
+        ppu.oam[8 * 4] = 100;
+        ppu.oam[9 * 4] = 9;
+        ppu.tick(cartridge.as_mut(), DOTS_PER_SCANLINE * 10);
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_OVERFLOW));
+
+        // False positive: no 9th sprite on the line, but sprite 9's tile is in range
+        ppu.oam[9 * 4] = 100;
+        ppu.oam[9 * 4 + 1] = 9;
+        ppu.tick(cartridge.as_mut(), DOTS_PER_SCANLINE * SCANLINES_PER_FRAME);
+        assert!(ppu.status.contains(StatusRegister::SPRITE_OVERFLOW));
+    }
+
+    #[test]
+    fn test_scroll_writes() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.write_to_control(0b10);
+        ppu.write_to_scroll(0x7D); // coarse X 15, fine X 5
+        ppu.write_to_scroll(0x5A); // coarse Y 11, fine Y 2
+
+        // 010 10 01011 01111
+        assert_eq!(ppu.temp_vram_address.get(), 0x296F);
+        assert_eq!(ppu.fine_x, 5);
+        assert!(!ppu.write_latch);
+    }
+
+    #[test]
+    fn test_scroll_and_address_share_write_latch() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.write_to_vram_address(0x21);
+        ppu.write_to_scroll(0x48); // second write: coarse Y 9, fine Y 0
+
+        assert_eq!(ppu.temp_vram_address.get(), 9 << 5);
+        assert!(!ppu.write_latch);
+
+        // $2006's second write copies t to v
+        ppu.write_to_vram_address(0x04);
+        ppu.write_to_vram_address(0x20);
+        assert_eq!(ppu.vram_address.get(), 0x0420);
+    }
+
+    #[test]
+    fn test_vram_address_increments() {
+        let mut address = VramAddress::new();
+        address.set_coarse_x(31);
+        address.increment_coarse_x();
+        assert_eq!(address.get(), 0x0400);
+
+        address.set_fine_y(7);
+        address.set_coarse_y(29);
+        address.increment_y();
+        assert_eq!(address.get(), 0x0C00);
+
+        address.set_fine_y(7);
+        address.set_coarse_y(31);
+        address.increment_y();
+        assert_eq!(address.get(), 0x0C00);
+    }
+
+    #[test]
+    fn test_scrolled_background() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        set_test_palette(&mut ppu);
+        let mut cartridge = new_solid_tile_cartridge();
+        ppu.vram[0x0021] = 1; // tile (1, 1)
+        ppu.write_to_scroll(4);
+        ppu.write_to_scroll(2);
+        ppu.write_to_mask(
+            (MaskRegister::SHOW_BACKGROUND | MaskRegister::LEFTMOST_8_BACKGROUND).bits(),
+        );
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        let tile = palette::SYSTEM_PALLETE[0x23];
+        let backdrop = palette::SYSTEM_PALLETE[0x01];
+        assert_eq!(ppu.frame.get_pixel(4, 6), tile);
+        assert_eq!(ppu.frame.get_pixel(11, 13), tile);
+        assert_eq!(ppu.frame.get_pixel(3, 6), backdrop);
+        assert_eq!(ppu.frame.get_pixel(4, 5), backdrop);
+        assert_eq!(ppu.frame.get_pixel(12, 13), backdrop);
+        assert_eq!(ppu.frame.get_pixel(11, 14), backdrop);
+    }
+
+    #[test]
+    fn test_palette_colors() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        set_test_palette(&mut ppu);
+        let mut cartridge = new_solid_tile_cartridge();
+        ppu.vram[0x0021] = 1; // tile (1, 1), palette 0
+        ppu.vram[0x0042] = 1; // tile (2, 2), palette 2
+        ppu.vram[0x03C0] = 0b1000_0000; // bottom right 2x2 tiles
+        ppu.palette_table[0x09] = 0x16;
+
+        ppu.oam[0..4].copy_from_slice(&[31, 1, 0b11, 40]); // palette 7
+        ppu.palette_table[0x1D] = 0x2A;
+        ppu.write_to_mask((MaskRegister::SHOW_BACKGROUND | MaskRegister::SHOW_SPRITES).bits());
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        assert_eq!(ppu.frame.get_pixel(0, 0), palette::SYSTEM_PALLETE[0x01]);
+        assert_eq!(ppu.frame.get_pixel(8, 8), palette::SYSTEM_PALLETE[0x23]);
+        assert_eq!(ppu.frame.get_pixel(16, 16), palette::SYSTEM_PALLETE[0x16]);
+        assert_eq!(ppu.frame.get_pixel(40, 32), palette::SYSTEM_PALLETE[0x2A]);
+    }
+
+    #[test]
+    fn test_palette_mirroring() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+
+        // $3F10 mirrors $3F00
+        ppu.write_to_vram_address(0x3F);
+        ppu.write_to_vram_address(0x10);
+        ppu.write_to_data(cartridge.as_mut(), 0x2C);
+        assert_eq!(ppu.palette_table[0x00], 0x2C);
+
+        // $3F24 mirrors $3F04, and palette reads aren't buffered
+        ppu.write_to_vram_address(0x3F);
+        ppu.write_to_vram_address(0x24);
+        ppu.write_to_data(cartridge.as_mut(), 0x15);
+        ppu.write_to_vram_address(0x3F);
+        ppu.write_to_vram_address(0x04);
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x15);
+
+        // $3F11 isn't mirrored
+        ppu.write_to_vram_address(0x3F);
+        ppu.write_to_vram_address(0x11);
+        ppu.write_to_data(cartridge.as_mut(), 0x30);
+        assert_eq!(ppu.palette_table[0x01], 0);
+        assert_eq!(ppu.palette_table[0x11], 0x30);
+    }
+
+    #[test]
+    fn test_vram_mirrors_above_3000() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.write_to_vram_address(0x33);
+        ppu.write_to_vram_address(0x05);
+        ppu.write_to_data(cartridge.as_mut(), 0x66);
+
+        assert_eq!(ppu.vram[0x0305], 0x66);
+    }
+
+    #[test]
+    fn test_greyscale() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.palette_table[0x00] = 0x16;
+        ppu.write_to_mask(MaskRegister::GREYSCALE.bits());
+
+        render_frame(&mut ppu, cartridge.as_mut());
+        assert_eq!(ppu.frame.get_pixel(0, 0), palette::SYSTEM_PALLETE[0x10]);
+
+        ppu.write_to_vram_address(0x3F);
+        ppu.write_to_vram_address(0x00);
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x10);
+    }
+
+    #[test]
+    fn test_pal_color_emphasis() {
+        // Bit 5 emphasizes green on PAL
+        let mut ppu = Ppu::new(Region::Pal);
+        let mut cartridge = new_empty_cartridge();
+        ppu.palette_table[0x00] = 0x30;
+        ppu.write_to_mask(MaskRegister::EMPHASIZE_RED.bits());
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        let (red, green, blue) = ppu.frame.get_pixel(0, 0);
+        assert_eq!(green, 0xFF);
+        assert!(red < 0xFF);
+        assert_eq!(red, blue);
+
+        // Bit 6 emphasizes red
+        ppu.write_to_mask(MaskRegister::EMPHASIZE_GREEN.bits());
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        let (red, green, blue) = ppu.frame.get_pixel(0, 0);
+        assert_eq!(red, 0xFF);
+        assert!(green < 0xFF);
+        assert_eq!(green, blue);
+    }
+
+    #[test]
+    fn test_color_emphasis() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.palette_table[0x00] = 0x30;
+        ppu.write_to_mask(MaskRegister::EMPHASIZE_RED.bits());
+
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        let (red, green, blue) = ppu.frame.get_pixel(0, 0);
+        assert_eq!(red, 0xFF);
+        assert!(green < 0xFF);
+        assert!(blue < 0xFF);
+        assert_eq!(green, blue);
+
+        ppu.write_to_mask(
+            (MaskRegister::EMPHASIZE_RED
+                | MaskRegister::EMPHASIZE_GREEN
+                | MaskRegister::EMPHASIZE_BLUE)
+                .bits(),
+        );
+        render_frame(&mut ppu, cartridge.as_mut());
+        let (red_all, green_all, blue_all) = ppu.frame.get_pixel(0, 0);
+        assert!(red_all < green);
+        assert_eq!(red_all, green_all);
+        assert_eq!(red_all, blue_all);
+    }
+
+    #[test]
+    fn test_left_column_clipping() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        set_test_palette(&mut ppu);
+        let mut cartridge = new_solid_tile_cartridge();
+        ppu.vram[0x0000] = 1; // tile (0, 0)
+        ppu.oam[0..4].copy_from_slice(&[15, 1, 0, 4]);
+
+        let show = MaskRegister::SHOW_BACKGROUND | MaskRegister::SHOW_SPRITES;
+        ppu.write_to_mask(show.bits());
+        render_frame(&mut ppu, cartridge.as_mut());
+
+        let backdrop = palette::SYSTEM_PALLETE[0x01];
+        let pattern = palette::SYSTEM_PALLETE[0x23];
+        assert_eq!(ppu.frame.get_pixel(7, 0), backdrop);
+        assert_eq!(ppu.frame.get_pixel(7, 16), backdrop);
+        assert_eq!(ppu.frame.get_pixel(8, 16), pattern);
+
+        ppu.write_to_mask(
+            (show | MaskRegister::LEFTMOST_8_BACKGROUND | MaskRegister::LEFTMOST_8_SPRITES).bits(),
+        );
+        render_frame(&mut ppu, cartridge.as_mut());
+        assert_eq!(ppu.frame.get_pixel(7, 0), pattern);
+        assert_eq!(ppu.frame.get_pixel(7, 16), pattern);
+    }
+
     #[test]
     fn test_ppu_vram_reads() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
         ppu.write_to_control(0);
         ppu.vram[0x0305] = 0x66;
 
         ppu.write_to_vram_address(0x23);
         ppu.write_to_vram_address(0x05);
 
-        ppu.read_from_data(); //load_into_buffer
+        ppu.read_from_data(cartridge.as_mut()); //load_into_buffer
         assert_eq!(ppu.vram_address.get(), 0x2306);
-        assert_eq!(ppu.read_from_data(), 0x66);
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x66);
     }
 
     #[test]
     fn test_ppu_vram_reads_cross_page() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
         ppu.write_to_control(0);
         ppu.vram[0x01ff] = 0x66;
         ppu.vram[0x0200] = 0x77;
@@ -529,14 +1825,15 @@ pub mod test {
         ppu.write_to_vram_address(0x21);
         ppu.write_to_vram_address(0xff);
 
-        ppu.read_from_data(); //load_into_buffer
-        assert_eq!(ppu.read_from_data(), 0x66);
-        assert_eq!(ppu.read_from_data(), 0x77);
+        ppu.read_from_data(cartridge.as_mut()); //load_into_buffer
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x66);
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x77);
     }
 
     #[test]
     fn test_ppu_vram_reads_step_32() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
         ppu.write_to_control(0b100);
         ppu.vram[0x01ff] = 0x66;
         ppu.vram[0x01ff + 32] = 0x77;
@@ -545,10 +1842,10 @@ pub mod test {
         ppu.write_to_vram_address(0x21);
         ppu.write_to_vram_address(0xff);
 
-        ppu.read_from_data(); //load_into_buffer
-        assert_eq!(ppu.read_from_data(), 0x66);
-        assert_eq!(ppu.read_from_data(), 0x77);
-        assert_eq!(ppu.read_from_data(), 0x88);
+        ppu.read_from_data(cartridge.as_mut()); //load_into_buffer
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x66);
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x77);
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x88);
     }
 
     // Horizontal: https://wiki.nesdev.com/w/index.php/Mirroring
@@ -556,28 +1853,29 @@ pub mod test {
     //   [0x2800 B ] [0x2C00 b ]
     #[test]
     fn test_vram_horizontal_mirror() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
         ppu.write_to_vram_address(0x24);
         ppu.write_to_vram_address(0x05);
 
-        ppu.write_to_data(0x66); //write to a
+        ppu.write_to_data(cartridge.as_mut(), 0x66); //write to a
 
         ppu.write_to_vram_address(0x28);
         ppu.write_to_vram_address(0x05);
 
-        ppu.write_to_data(0x77); //write to B
+        ppu.write_to_data(cartridge.as_mut(), 0x77); //write to B
 
         ppu.write_to_vram_address(0x20);
         ppu.write_to_vram_address(0x05);
 
-        ppu.read_from_data(); //load into buffer
-        assert_eq!(ppu.read_from_data(), 0x66); //read from A
+        ppu.read_from_data(cartridge.as_mut()); //load into buffer
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x66); //read from A
 
         ppu.write_to_vram_address(0x2C);
         ppu.write_to_vram_address(0x05);
 
-        ppu.read_from_data(); //load into buffer
-        assert_eq!(ppu.read_from_data(), 0x77); //read from b
+        ppu.read_from_data(cartridge.as_mut()); //load into buffer
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x77); //read from b
     }
 
     // Vertical: https://wiki.nesdev.com/w/index.php/Mirroring
@@ -585,69 +1883,96 @@ pub mod test {
     //   [0x2800 a ] [0x2C00 b ]
     #[test]
     fn test_vram_vertical_mirror() {
-        let mut ppu = Ppu::new_chr_rom_mirroring(vec![0; 2048], Mirroring::Vertical);
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut rom = Rom::new_empty();
+        rom.mirroring = Mirroring::Vertical;
+        let mut cartridge = cartridge::new(rom);
 
         ppu.write_to_vram_address(0x20);
         ppu.write_to_vram_address(0x05);
 
-        ppu.write_to_data(0x66); //write to A
+        ppu.write_to_data(cartridge.as_mut(), 0x66); //write to A
 
         ppu.write_to_vram_address(0x2C);
         ppu.write_to_vram_address(0x05);
 
-        ppu.write_to_data(0x77); //write to b
+        ppu.write_to_data(cartridge.as_mut(), 0x77); //write to b
 
         ppu.write_to_vram_address(0x28);
         ppu.write_to_vram_address(0x05);
 
-        ppu.read_from_data(); //load into buffer
-        assert_eq!(ppu.read_from_data(), 0x66); //read from a
+        ppu.read_from_data(cartridge.as_mut()); //load into buffer
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x66); //read from a
 
         ppu.write_to_vram_address(0x24);
         ppu.write_to_vram_address(0x05);
 
-        ppu.read_from_data(); //load into buffer
-        assert_eq!(ppu.read_from_data(), 0x77); //read from B
+        ppu.read_from_data(cartridge.as_mut()); //load into buffer
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x77); //read from B
+    }
+
+    // Four-screen: https://wiki.nesdev.com/w/index.php/Mirroring
+    //   [0x2000 A ] [0x2400 B ]
+    //   [0x2800 C ] [0x2C00 D ]
+    #[test]
+    fn test_vram_four_screen() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut rom = Rom::new_empty();
+        rom.mirroring = Mirroring::FourScreen;
+        let mut cartridge = cartridge::new(rom);
+
+        for (nametable, value) in [(0x20, 0x66), (0x24, 0x77), (0x28, 0x88), (0x2C, 0x99)] {
+            ppu.write_to_vram_address(nametable);
+            ppu.write_to_vram_address(0x05);
+            ppu.write_to_data(cartridge.as_mut(), value);
+        }
+
+        assert_eq!(ppu.vram[0x0005], 0x66);
+        assert_eq!(ppu.vram[0x0405], 0x77);
+        assert_eq!(ppu.vram[0x0805], 0x88);
+        assert_eq!(ppu.vram[0x0C05], 0x99);
     }
 
     #[test]
     fn test_read_status_resets_latch() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
         ppu.vram[0x0305] = 0x66;
 
         ppu.write_to_vram_address(0x21);
         ppu.write_to_vram_address(0x23);
         ppu.write_to_vram_address(0x05);
 
-        ppu.read_from_data(); //load_into_buffer
-        assert_ne!(ppu.read_from_data(), 0x66);
+        ppu.read_from_data(cartridge.as_mut()); //load_into_buffer
+        assert_ne!(ppu.read_from_data(cartridge.as_mut()), 0x66);
 
         ppu.read_from_status();
 
         ppu.write_to_vram_address(0x23);
         ppu.write_to_vram_address(0x05);
 
-        ppu.read_from_data(); //load_into_buffer
-        assert_eq!(ppu.read_from_data(), 0x66);
+        ppu.read_from_data(cartridge.as_mut()); //load_into_buffer
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x66);
     }
 
     #[test]
     fn test_ppu_vram_mirroring() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
         ppu.write_to_control(0);
         ppu.vram[0x0305] = 0x66;
 
         ppu.write_to_vram_address(0x63); //0x6305 -> 0x2305
         ppu.write_to_vram_address(0x05);
 
-        ppu.read_from_data(); //load into_buffer
-        assert_eq!(ppu.read_from_data(), 0x66);
+        ppu.read_from_data(cartridge.as_mut()); //load into_buffer
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x66);
         // assert_eq!(ppu.addr.read(), 0x0306)
     }
 
     #[test]
     fn test_read_status_resets_vblank() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
         ppu.status.insert(StatusRegister::VBLANK_STARTED);
 
         let status = ppu.read_from_status();
@@ -656,9 +1981,215 @@ pub mod test {
         assert_eq!(ppu.status.bits() >> 7, 0);
     }
 
+    /**
+     * Ticks until the PPU is about to process the given dot
+     */
+    fn tick_to(ppu: &mut Ppu, cartridge: &mut dyn Cartridge, scanline: u32, dot: u32) {
+        while (ppu.scanline, ppu.cycles) != (scanline, dot) {
+            ppu.tick(cartridge, 1);
+        }
+    }
+
+    #[test]
+    fn test_vblank_timing() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.write_to_control(ControlRegister::GENERATE_NMI.bits());
+
+        tick_to(&mut ppu, cartridge.as_mut(), VBLANK_SCANLINE, 1);
+        assert!(!ppu.status.contains(StatusRegister::VBLANK_STARTED));
+        assert!(!ppu.nmi_interrupt);
+
+        ppu.tick(cartridge.as_mut(), 1);
+        assert!(ppu.status.contains(StatusRegister::VBLANK_STARTED));
+        assert!(poll_nmi_status(&mut ppu));
+
+        // Only one NMI per vblank
+        ppu.tick(cartridge.as_mut(), DOTS_PER_SCANLINE);
+        assert!(!poll_nmi_status(&mut ppu));
+
+        tick_to(&mut ppu, cartridge.as_mut(), PRE_RENDER_SCANLINE, 2);
+        assert!(!ppu.status.contains(StatusRegister::VBLANK_STARTED));
+    }
+
+    #[test]
+    fn test_status_read_before_vblank_suppresses_it() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.write_to_control(ControlRegister::GENERATE_NMI.bits());
+
+        tick_to(&mut ppu, cartridge.as_mut(), VBLANK_SCANLINE, 1);
+        assert_eq!(ppu.read_from_status() >> 7, 0);
+
+        ppu.tick(cartridge.as_mut(), 1);
+        assert!(!ppu.status.contains(StatusRegister::VBLANK_STARTED));
+        assert!(!poll_nmi_status(&mut ppu));
+    }
+
+    #[test]
+    fn test_status_read_at_vblank_suppresses_nmi() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.write_to_control(ControlRegister::GENERATE_NMI.bits());
+
+        tick_to(&mut ppu, cartridge.as_mut(), VBLANK_SCANLINE, 3);
+        assert_eq!(ppu.read_from_status() >> 7, 1);
+        assert!(!poll_nmi_status(&mut ppu));
+
+        // On the next frame, a read 3 dots after vblank starts doesn't affect the NMI
+        tick_to(&mut ppu, cartridge.as_mut(), VBLANK_SCANLINE + 1, 0);
+        tick_to(&mut ppu, cartridge.as_mut(), VBLANK_SCANLINE, 4);
+        ppu.read_from_status();
+        assert!(poll_nmi_status(&mut ppu));
+    }
+
+    /**
+     * Ticks to the given dot of the vblank line, runs the register access,
+     * then ticks past the race.
+     * Returns whether VBLANK_STARTED was set afterwards, and whether an NMI
+     * was raised.
+     */
+    fn vblank_race(dot: u32, nmi_enabled: bool, access: impl FnOnce(&mut Ppu)) -> (bool, bool) {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        if nmi_enabled {
+            ppu.write_to_control(ControlRegister::GENERATE_NMI.bits());
+        }
+
+        tick_to(&mut ppu, cartridge.as_mut(), VBLANK_SCANLINE, dot);
+        access(&mut ppu);
+        tick_to(&mut ppu, cartridge.as_mut(), VBLANK_SCANLINE, 10);
+        (
+            ppu.status.contains(StatusRegister::VBLANK_STARTED),
+            poll_nmi_status(&mut ppu),
+        )
+    }
+
+    #[test]
+    fn test_status_read_race() {
+        // (dot, flag read, flag set afterwards, NMI)
+        let expected = [
+            (0, false, true, true),
+            (1, false, false, false),
+            (2, true, false, false),
+            (3, true, false, false),
+            (4, true, false, true),
+        ];
+        for (dot, flag_read, flag_after, nmi) in expected {
+            let mut status = 0;
+            let result = vblank_race(dot, true, |ppu| status = ppu.read_from_status());
+            assert_eq!(status >> 7 == 1, flag_read, "dot {}", dot);
+            assert_eq!(result, (flag_after, nmi), "dot {}", dot);
+        }
+    }
+
+    #[test]
+    fn test_control_write_race() {
+        // Disabling NMI before vblank starts, or on the dot after, stops it
+        let expected = [(0, false), (1, false), (2, false), (3, true)];
+        for (dot, nmi) in expected {
+            let result = vblank_race(dot, true, |ppu| ppu.write_to_control(0));
+            assert_eq!(result, (true, nmi), "dot {}", dot);
+        }
+
+        // Enabling NMI around vblank starting always raises it
+        for dot in 0..=3 {
+            let result = vblank_race(dot, false, |ppu| {
+                ppu.write_to_control(ControlRegister::GENERATE_NMI.bits())
+            });
+            assert_eq!(result, (true, true), "dot {}", dot);
+        }
+    }
+
+    #[test]
+    fn test_nmi_enable_during_vblank() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        tick_to(&mut ppu, cartridge.as_mut(), VBLANK_SCANLINE + 1, 0);
+        assert!(!poll_nmi_status(&mut ppu));
+
+        // Each time NMI is enabled during vblank, it's raised again
+        ppu.write_to_control(ControlRegister::GENERATE_NMI.bits());
+        assert!(poll_nmi_status(&mut ppu));
+        ppu.write_to_control(0);
+        ppu.write_to_control(ControlRegister::GENERATE_NMI.bits());
+        assert!(poll_nmi_status(&mut ppu));
+
+        // Not once vblank has been read
+        ppu.write_to_control(0);
+        ppu.read_from_status();
+        ppu.write_to_control(ControlRegister::GENERATE_NMI.bits());
+        assert!(!poll_nmi_status(&mut ppu));
+    }
+
+    #[test]
+    fn test_odd_frame_dot_skip() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        let mut frame_lengths = vec![];
+        for _ in 0..4 {
+            let mut dots = 0;
+            while !ppu.tick(cartridge.as_mut(), 1) {
+                dots += 1;
+            }
+            frame_lengths.push(dots + 1);
+            ppu.write_to_mask(MaskRegister::SHOW_BACKGROUND.bits());
+        }
+
+        let frame_dots = DOTS_PER_SCANLINE * SCANLINES_PER_FRAME;
+        assert_eq!(
+            frame_lengths,
+            vec![frame_dots, frame_dots - 1, frame_dots, frame_dots - 1]
+        );
+    }
+
+    #[test]
+    fn test_region_frame_timing() {
+        for region in [Region::Pal, Region::Dendy] {
+            let mut ppu = Ppu::new(region);
+            let mut cartridge = new_empty_cartridge();
+            ppu.write_to_mask(MaskRegister::SHOW_BACKGROUND.bits());
+
+            tick_to(&mut ppu, cartridge.as_mut(), region.vblank_scanline(), 1);
+            assert!(!ppu.status.contains(StatusRegister::VBLANK_STARTED));
+            ppu.tick(cartridge.as_mut(), 1);
+            assert!(ppu.status.contains(StatusRegister::VBLANK_STARTED));
+
+            // 312 lines, with no odd-frame dot skip
+            while !ppu.tick(cartridge.as_mut(), 1) {}
+            for _ in 0..2 {
+                let mut dots = 1;
+                while !ppu.tick(cartridge.as_mut(), 1) {
+                    dots += 1;
+                }
+                assert_eq!(dots, DOTS_PER_SCANLINE * 312);
+            }
+        }
+
+        assert_eq!(Region::Pal.vblank_scanline(), VBLANK_SCANLINE);
+        assert_eq!(Region::Dendy.vblank_scanline(), VBLANK_SCANLINE + 50);
+    }
+
+    #[test]
+    fn test_pal_clock_ratio() {
+        let mut ppu = Ppu::new(Region::Pal);
+        let mut cartridge = new_empty_cartridge();
+        for _ in 0..5 {
+            ppu.tick_cpu_cycles(cartridge.as_mut(), 1);
+        }
+        assert_eq!((ppu.scanline, ppu.cycles), (0, 16));
+
+        ppu.tick_cpu_cycles(cartridge.as_mut(), 10);
+        assert_eq!((ppu.scanline, ppu.cycles), (0, 48));
+
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.tick_cpu_cycles(cartridge.as_mut(), 5);
+        assert_eq!((ppu.scanline, ppu.cycles), (0, 15));
+    }
+
     #[test]
     fn test_oam_read_write() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
         ppu.write_to_oam_address(0x10);
         ppu.write_to_oam_data(0x66);
         ppu.write_to_oam_data(0x77);
@@ -670,9 +2201,83 @@ pub mod test {
         assert_eq!(ppu.read_from_oam_data(), 0x77);
     }
 
+    #[test]
+    fn test_oam_attribute_read_masking() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.write_to_oam_address(0x10);
+        for _ in 0..4 {
+            ppu.write_to_oam_data(0xFF);
+        }
+
+        ppu.write_to_oam_address(0x11);
+        assert_eq!(ppu.read_from_oam_data(), 0xFF);
+        ppu.write_to_oam_address(0x12);
+        assert_eq!(ppu.read_from_oam_data(), 0xE3);
+    }
+
+    #[test]
+    fn test_io_latch_decay() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.write_to_io_latch(0xA5);
+        assert_eq!(ppu.read_from_io_latch(), 0xA5);
+
+        for _ in 0..IO_LATCH_DECAY_FRAMES - 1 {
+            ppu.tick(cartridge.as_mut(), DOTS_PER_SCANLINE * SCANLINES_PER_FRAME);
+        }
+        assert_eq!(ppu.read_from_io_latch(), 0xA5);
+
+        ppu.tick(cartridge.as_mut(), DOTS_PER_SCANLINE * SCANLINES_PER_FRAME);
+        assert_eq!(ppu.read_from_io_latch(), 0);
+    }
+
+    #[test]
+    fn test_io_latch_bits_decay_separately() {
+        let mut latch = IoLatch::new();
+        latch.drive(0xFF, 0xFF, 0);
+        latch.drive(0xA0, 0xE0, 20);
+
+        assert_eq!(latch.get(IO_LATCH_DECAY_FRAMES - 1), 0xBF);
+        assert_eq!(latch.get(IO_LATCH_DECAY_FRAMES), 0xA0);
+        assert_eq!(latch.get(20 + IO_LATCH_DECAY_FRAMES), 0);
+    }
+
+    #[test]
+    fn test_status_low_bits_are_open_bus() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        ppu.write_to_io_latch(0x5F);
+        ppu.status.insert(StatusRegister::VBLANK_STARTED);
+
+        assert_eq!(ppu.read_from_status(), 0x9F);
+        assert_eq!(ppu.read_from_status(), 0x1F);
+        assert_eq!(ppu.read_from_io_latch(), 0x1F);
+    }
+
+    #[test]
+    fn test_palette_read_fills_buffer_from_nametable() {
+        let mut ppu = Ppu::new(Region::Ntsc);
+        let mut cartridge = new_empty_cartridge();
+        ppu.write_to_vram_address(0x2F);
+        ppu.write_to_vram_address(0x05);
+        ppu.write_to_data(cartridge.as_mut(), 0x42);
+        ppu.write_to_vram_address(0x3F);
+        ppu.write_to_vram_address(0x05);
+        ppu.write_to_data(cartridge.as_mut(), 0x11);
+
+        // The top 2 bits of a palette read are open bus
+        ppu.write_to_io_latch(0xC0);
+        ppu.write_to_vram_address(0x3F);
+        ppu.write_to_vram_address(0x05);
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0xD1);
+
+        ppu.write_to_vram_address(0x00);
+        ppu.write_to_vram_address(0x00);
+        assert_eq!(ppu.read_from_data(cartridge.as_mut()), 0x42);
+    }
+
     #[test]
     fn test_oam_dma() {
-        let mut ppu = Ppu::new_empty_rom();
+        let mut ppu = Ppu::new(Region::Ntsc);
 
         let mut data = [0x66; 256];
         data[0] = 0x77;
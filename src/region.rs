@@ -0,0 +1,135 @@
+use std::{error, fmt, str::FromStr};
+
+use crate::rom::Timing;
+
+/**
+ * The console variant being emulated. PAL consoles and Dendy famiclones have
+ * 312-line frames, and PAL runs its CPU slower relative to the PPU.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Region {
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl Region {
+    pub const fn scanlines_per_frame(&self) -> u32 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    /**
+     * The scanline VBLANK_STARTED is set and the NMI raised on, at dot 1.
+     * Dendy has 51 idle lines after the picture before vblank, and 20 lines of
+     * vblank like NTSC. PAL has a 70-line vblank instead.
+     */
+    pub const fn vblank_scanline(&self) -> u32 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    pub const fn pre_render_scanline(&self) -> u32 {
+        self.scanlines_per_frame() - 1
+    }
+
+    /**
+     * Only NTSC skips a dot on odd frames
+     */
+    pub const fn skips_odd_frame_dot(&self) -> bool {
+        matches!(self, Region::Ntsc)
+    }
+
+    /**
+     * PAL and Dendy PPUs swap the red and green emphasis bits
+     */
+    pub const fn swaps_red_green_emphasis(&self) -> bool {
+        !matches!(self, Region::Ntsc)
+    }
+
+    /**
+     * CPU cycles per second
+     */
+    pub const fn cpu_clock_rate(&self) -> u32 {
+        match self {
+            Region::Ntsc => 1_789_773,
+            Region::Pal => 1_662_607,
+            Region::Dendy => 1_773_448,
+        }
+    }
+
+    /**
+     * PPU dots per CPU cycle, as (dots, cpu cycles). PAL runs 16 dots every
+     * 5 CPU cycles.
+     */
+    pub const fn ppu_clock_ratio(&self) -> (u32, u32) {
+        match self {
+            Region::Ntsc | Region::Dendy => (3, 1),
+            Region::Pal => (16, 5),
+        }
+    }
+}
+
+/**
+ * Multi-region ROMs run as NTSC
+ */
+impl From<Timing> for Region {
+    fn from(timing: Timing) -> Self {
+        match timing {
+            Timing::Ntsc | Timing::MultiRegion => Region::Ntsc,
+            Timing::Pal => Region::Pal,
+            Timing::Dendy => Region::Dendy,
+        }
+    }
+}
+
+/**
+ * A region name that isn't "ntsc", "pal" or "dendy"
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UnknownRegion(pub String);
+
+impl fmt::Display for UnknownRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown region: {}", self.0)
+    }
+}
+
+impl error::Error for UnknownRegion {}
+
+impl FromStr for Region {
+    type Err = UnknownRegion;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "ntsc" => Ok(Region::Ntsc),
+            "pal" => Ok(Region::Pal),
+            "dendy" => Ok(Region::Dendy),
+            _ => Err(UnknownRegion(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{region::Region, rom::Timing};
+
+    #[test]
+    fn test_region_from_timing() {
+        assert_eq!(Region::from(Timing::Ntsc), Region::Ntsc);
+        assert_eq!(Region::from(Timing::MultiRegion), Region::Ntsc);
+        assert_eq!(Region::from(Timing::Pal), Region::Pal);
+        assert_eq!(Region::from(Timing::Dendy), Region::Dendy);
+    }
+
+    #[test]
+    fn test_region_from_str() {
+        assert_eq!("pal".parse(), Ok(Region::Pal));
+        assert_eq!("Dendy".parse(), Ok(Region::Dendy));
+        assert!("secam".parse::<Region>().is_err());
+    }
+}
@@ -1,59 +1,272 @@
+use std::{error, fmt};
+
 use crate::config::{CHR_ROM_PAGE_SIZE, PROGRAM_ROM_PAGE_SIZE};
 
 const I_NES_IDENTIFIER_BYTES: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+
+/**
+ * Reasons a ROM file can't be loaded
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RomError {
+    /**
+     * The file is smaller than the 16-byte header
+     */
+    TooShort {
+        actual: usize,
+    },
+    /**
+     * The file doesn't start with "NES" followed by MS-DOS end-of-file
+     */
+    BadMagic,
+    /**
+     * The header's version bits are neither iNES nor NES 2.0
+     */
+    UnsupportedVersion,
+    UnsupportedMapper(u16),
+    /**
+     * The file is smaller than the sizes declared in its header
+     */
+    Truncated {
+        expected: usize,
+        actual: usize,
+    },
+    /**
+     * The sizes declared in the header are too large to address
+     */
+    TooLarge,
+    /**
+     * The header declares no PRG-ROM
+     */
+    NoProgramRom,
+    /**
+     * The PRG-ROM isn't a whole number of the mapper's PRG banks
+     */
+    PartialProgramBank {
+        size: usize,
+        bank_size: usize,
+    },
+    /**
+     * The CHR-ROM isn't a whole number of the mapper's CHR banks
+     */
+    PartialChrBank {
+        size: usize,
+        bank_size: usize,
+    },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::TooShort { actual } => write!(
+                f,
+                "Rom file is too short for an iNES header: {} bytes",
+                actual
+            ),
+            RomError::BadMagic => write!(f, "Rom file is not an iNES file"),
+            RomError::UnsupportedVersion => write!(f, "Unsupported iNES version"),
+            RomError::UnsupportedMapper(mapper) => write!(f, "Unsupported mapper: {}", mapper),
+            RomError::Truncated { expected, actual } => write!(
+                f,
+                "Rom file is truncated: expected {} bytes, found {}",
+                expected, actual
+            ),
+            RomError::TooLarge => write!(f, "Rom file declares sizes too large to load"),
+            RomError::NoProgramRom => write!(f, "Rom file has no PRG-ROM"),
+            RomError::PartialProgramBank { size, bank_size } => write!(
+                f,
+                "PRG-ROM size {} is not a multiple of the mapper's {} byte banks",
+                size, bank_size
+            ),
+            RomError::PartialChrBank { size, bank_size } => write!(
+                f,
+                "CHR-ROM size {} is not a multiple of the mapper's {} byte banks",
+                size, bank_size
+            ),
+        }
+    }
+}
+
+impl error::Error for RomError {}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Mirroring {
     Vertical,
     Horizontal,
     FourScreen,
+    SingleScreenLower,
+    SingleScreenUpper,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Mapper {
     Zero,
+    One,
+    Two,
+    Three,
+    Four,
+    Seven,
+    SixtySix,
 }
 
-impl TryFrom<u8> for Mapper {
-    type Error = String;
+impl TryFrom<u16> for Mapper {
+    type Error = RomError;
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
         match value {
             0 => Ok(Mapper::Zero),
-            _ => Err("Unsupported mapper: {}".replace("{}", &value.to_string())),
+            1 => Ok(Mapper::One),
+            2 => Ok(Mapper::Two),
+            3 => Ok(Mapper::Three),
+            4 => Ok(Mapper::Four),
+            7 => Ok(Mapper::Seven),
+            66 => Ok(Mapper::SixtySix),
+            _ => Err(RomError::UnsupportedMapper(value)),
+        }
+    }
+}
+
+impl Mapper {
+    /**
+     * Size of the smallest PRG-ROM bank the mapper switches, in bytes
+     */
+    pub const fn program_bank_size(&self) -> usize {
+        match self {
+            Mapper::Four => 0x2000,
+            Mapper::Seven | Mapper::SixtySix => 0x8000,
+            Mapper::Zero | Mapper::One | Mapper::Two | Mapper::Three => {
+                PROGRAM_ROM_PAGE_SIZE as usize
+            }
+        }
+    }
+
+    /**
+     * Size of the smallest CHR-ROM bank the mapper switches, in bytes
+     */
+    pub const fn chr_bank_size(&self) -> usize {
+        match self {
+            Mapper::One => 0x1000,
+            Mapper::Four => 0x0400,
+            Mapper::Zero | Mapper::Two | Mapper::Three | Mapper::Seven | Mapper::SixtySix => {
+                CHR_ROM_PAGE_SIZE as usize
+            }
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RomFormat {
+    INes,
+    Nes2,
+}
+
+/**
+ * CPU/PPU timing the ROM was made for
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    Playchoice10,
+    /**
+     * NES 2.0 extended console type, from header byte 13
+     */
+    Extended(u8),
+}
+
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub struct Rom {
     pub program_rom: Vec<u8>,
     pub chr_rom: Vec<u8>,
     pub mirroring: Mirroring,
     pub mapper: Mapper,
+    pub has_battery: bool,
+    /**
+     * 512 bytes loaded into PRG-RAM at $7000 before the game starts
+     */
+    pub trainer: Option<Vec<u8>>,
+
+    pub format: RomFormat,
+    pub mapper_number: u16,
+    pub submapper: u8,
+    /**
+     * Volatile PRG-RAM, in bytes
+     */
+    pub program_ram_size: usize,
+    /**
+     * Battery-backed PRG-RAM, in bytes
+     */
+    pub program_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    /**
+     * NES 2.0 default expansion device. 0 if unspecified, 1 for standard controllers.
+     */
+    pub expansion_device: u8,
 }
 
 impl Rom {
-    pub fn new(rom_bytes: &Vec<u8>) -> Result<Rom, String> {
+    /**
+     * Parses an iNES or NES 2.0 file.
+     *
+     * Header layout:
+     * 0-3: "NES" followed by MS-DOS end-of-file
+     * 4:   PRG-ROM size, LSB
+     * 5:   CHR-ROM size, LSB
+     * 6:   Control byte 1: mapper D3..D0, four-screen, trainer, battery, mirroring
+     * 7:   Control byte 2: mapper D7..D4, NES 2.0 identifier, console type
+     * 8:   (NES 2.0) Mapper D11..D8, submapper
+     * 9:   (NES 2.0) PRG-ROM / CHR-ROM size MSB
+     * 10:  (NES 2.0) PRG-RAM / PRG-NVRAM shift count
+     * 11:  (NES 2.0) CHR-RAM / CHR-NVRAM shift count
+     * 12:  (NES 2.0) CPU/PPU timing
+     * 13:  (NES 2.0) Vs. System type, or extended console type
+     * 14:  (NES 2.0) Miscellaneous ROMs
+     * 15:  (NES 2.0) Default expansion device
+     */
+    pub fn new(rom_bytes: &[u8]) -> Result<Rom, RomError> {
+        if rom_bytes.len() < HEADER_SIZE {
+            return Err(RomError::TooShort {
+                actual: rom_bytes.len(),
+            });
+        }
+
         let i_nes_identifier_bytes = &rom_bytes[0..4];
-        let program_rom_banks = rom_bytes[4];
-        let chr_rom_banks = rom_bytes[5];
+        let program_rom_size_lsb = rom_bytes[4];
+        let chr_rom_size_lsb = rom_bytes[5];
         let control_byte_1 = rom_bytes[6];
         let control_byte_2 = rom_bytes[7];
+        let mapper_byte = rom_bytes[8];
+        let rom_size_msb_byte = rom_bytes[9];
+        let program_ram_byte = rom_bytes[10];
+        let chr_ram_byte = rom_bytes[11];
+        let timing_byte = rom_bytes[12];
+        let console_type_byte = rom_bytes[13];
+        let expansion_device_byte = rom_bytes[15];
 
         if i_nes_identifier_bytes != I_NES_IDENTIFIER_BYTES {
-            return Err("Rom file is not an iNES file".to_string());
+            return Err(RomError::BadMagic);
         }
 
-        let mapper_byte = (control_byte_2 & 0xF0) | (control_byte_1 >> 4);
-        let mapper = Mapper::try_from(mapper_byte)?;
-
-        let i_nes_version = control_byte_2 & 0x0F;
-        if i_nes_version != 0 {
-            return Err("Only iNES v1.0 files are supported".to_string());
-        }
+        let format = match (control_byte_2 >> 2) & 0b11 {
+            0b00 => RomFormat::INes,
+            0b10 => RomFormat::Nes2,
+            _ => return Err(RomError::UnsupportedVersion),
+        };
 
-        let four_screen_mirroring = ((control_byte_1 & 0x0F) >> 4) != 0;
+        let four_screen_mirroring = (control_byte_1 & 0b0000_1000) != 0;
         let vertical_mirroring = (control_byte_1 & 1) != 0;
         let mirroring = match (four_screen_mirroring, vertical_mirroring) {
             (true, _) => Mirroring::FourScreen,
@@ -61,20 +274,409 @@ impl Rom {
             (false, false) => Mirroring::Horizontal,
         };
 
-        let program_rom_size = program_rom_banks as usize * PROGRAM_ROM_PAGE_SIZE as usize;
-        let chr_rom_size = chr_rom_banks as usize * CHR_ROM_PAGE_SIZE as usize;
+        let has_battery = (control_byte_1 & 0b0000_0010) != 0;
+
+        let console_type = match control_byte_2 & 0b11 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(console_type_byte & 0x0F),
+        };
+
+        let rom_info = match format {
+            RomFormat::INes => {
+                // Some old dumps have junk in bytes 12-15, which also corrupts mapper D7..D4
+                let junk_header = rom_bytes[12..16].iter().any(|&byte| byte != 0);
+                let mapper_high_nibble = if junk_header {
+                    0
+                } else {
+                    control_byte_2 & 0xF0
+                };
+                let mapper_number = (mapper_high_nibble | (control_byte_1 >> 4)) as u16;
+                let default_ram_size = 0x2000;
+                // iNES doesn't give the PRG-RAM size. NROM, MMC1 and MMC3 boards
+                // usually have 8KB, other boards only if it's battery-backed.
+                let program_ram_size = match (has_battery, mapper_number) {
+                    (true, _) | (false, 0 | 1 | 4) => default_ram_size,
+                    _ => 0,
+                };
+
+                RomInfo {
+                    mapper_number,
+                    submapper: 0,
+                    program_rom_size: program_rom_size_lsb as usize
+                        * PROGRAM_ROM_PAGE_SIZE as usize,
+                    chr_rom_size: chr_rom_size_lsb as usize * CHR_ROM_PAGE_SIZE as usize,
+                    program_ram_size: if has_battery { 0 } else { program_ram_size },
+                    program_nvram_size: if has_battery { program_ram_size } else { 0 },
+                    chr_ram_size: if chr_rom_size_lsb == 0 {
+                        default_ram_size
+                    } else {
+                        0
+                    },
+                    chr_nvram_size: 0,
+                    timing: if rom_bytes[9] & 1 != 0 && !junk_header {
+                        Timing::Pal
+                    } else {
+                        Timing::Ntsc
+                    },
+                    expansion_device: 0,
+                }
+            }
+            RomFormat::Nes2 => RomInfo {
+                mapper_number: ((mapper_byte & 0x0F) as u16) << 8
+                    | (control_byte_2 & 0xF0) as u16
+                    | (control_byte_1 >> 4) as u16,
+                submapper: mapper_byte >> 4,
+                program_rom_size: nes_2_rom_size(
+                    program_rom_size_lsb,
+                    rom_size_msb_byte & 0x0F,
+                    PROGRAM_ROM_PAGE_SIZE as usize,
+                )
+                .ok_or(RomError::TooLarge)?,
+                chr_rom_size: nes_2_rom_size(
+                    chr_rom_size_lsb,
+                    rom_size_msb_byte >> 4,
+                    CHR_ROM_PAGE_SIZE as usize,
+                )
+                .ok_or(RomError::TooLarge)?,
+                program_ram_size: nes_2_ram_size(program_ram_byte & 0x0F),
+                program_nvram_size: nes_2_ram_size(program_ram_byte >> 4),
+                chr_ram_size: nes_2_ram_size(chr_ram_byte & 0x0F),
+                chr_nvram_size: nes_2_ram_size(chr_ram_byte >> 4),
+                timing: match timing_byte & 0b11 {
+                    0 => Timing::Ntsc,
+                    1 => Timing::Pal,
+                    2 => Timing::MultiRegion,
+                    _ => Timing::Dendy,
+                },
+                expansion_device: expansion_device_byte & 0b0011_1111,
+            },
+        };
+
+        let mapper = Mapper::try_from(rom_info.mapper_number)?;
 
-        let has_trainer = ((control_byte_1 & 0b0000_0100) >> 3) != 0;
+        let program_rom_size = rom_info.program_rom_size;
+        let chr_rom_size = rom_info.chr_rom_size;
 
-        let program_rom_start = 16 + if has_trainer { 500 } else { 0 };
-        let chr_rom_start = program_rom_start + program_rom_size;
+        // The mappers index their banks modulo the bank count, so there must be at
+        // least one PRG bank, and no partial banks. No CHR-ROM means CHR-RAM.
+        if program_rom_size == 0 {
+            return Err(RomError::NoProgramRom);
+        }
+        if program_rom_size % mapper.program_bank_size() != 0 {
+            return Err(RomError::PartialProgramBank {
+                size: program_rom_size,
+                bank_size: mapper.program_bank_size(),
+            });
+        }
+        if chr_rom_size % mapper.chr_bank_size() != 0 {
+            return Err(RomError::PartialChrBank {
+                size: chr_rom_size,
+                bank_size: mapper.chr_bank_size(),
+            });
+        }
+
+        let has_trainer = (control_byte_1 & 0b0000_0100) != 0;
+
+        let program_rom_start = HEADER_SIZE + if has_trainer { TRAINER_SIZE } else { 0 };
+        let chr_rom_start = program_rom_start
+            .checked_add(program_rom_size)
+            .ok_or(RomError::TooLarge)?;
+        let chr_rom_end = chr_rom_start
+            .checked_add(chr_rom_size)
+            .ok_or(RomError::TooLarge)?;
+
+        if rom_bytes.len() < chr_rom_end {
+            return Err(RomError::Truncated {
+                expected: chr_rom_end,
+                actual: rom_bytes.len(),
+            });
+        }
 
         Ok(Rom {
-            program_rom: rom_bytes[program_rom_start..(program_rom_start + program_rom_size)]
-                .to_vec(),
-            chr_rom: rom_bytes[chr_rom_start..(chr_rom_start + chr_rom_size)].to_vec(),
+            program_rom: rom_bytes[program_rom_start..chr_rom_start].to_vec(),
+            chr_rom: rom_bytes[chr_rom_start..chr_rom_end].to_vec(),
             mirroring,
             mapper,
+            has_battery,
+            trainer: has_trainer.then(|| rom_bytes[HEADER_SIZE..program_rom_start].to_vec()),
+
+            format,
+            mapper_number: rom_info.mapper_number,
+            submapper: rom_info.submapper,
+            program_ram_size: rom_info.program_ram_size,
+            program_nvram_size: rom_info.program_nvram_size,
+            chr_ram_size: rom_info.chr_ram_size,
+            chr_nvram_size: rom_info.chr_nvram_size,
+            timing: rom_info.timing,
+            console_type,
+            expansion_device: rom_info.expansion_device,
         })
     }
+
+    #[cfg(test)]
+    pub fn new_empty() -> Rom {
+        Rom {
+            program_rom: vec![0; PROGRAM_ROM_PAGE_SIZE as usize],
+            chr_rom: vec![0; CHR_ROM_PAGE_SIZE as usize],
+            mirroring: Mirroring::Horizontal,
+            mapper: Mapper::Zero,
+            has_battery: false,
+            trainer: None,
+
+            format: RomFormat::INes,
+            mapper_number: 0,
+            submapper: 0,
+            program_ram_size: 0x2000,
+            program_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+            timing: Timing::Ntsc,
+            console_type: ConsoleType::Nes,
+            expansion_device: 0,
+        }
+    }
+}
+
+/**
+ * The parts of the header whose meaning differs between iNES and NES 2.0
+ */
+struct RomInfo {
+    mapper_number: u16,
+    submapper: u8,
+    program_rom_size: usize,
+    chr_rom_size: usize,
+    program_ram_size: usize,
+    program_nvram_size: usize,
+    chr_ram_size: usize,
+    chr_nvram_size: usize,
+    timing: Timing,
+    expansion_device: u8,
+}
+
+/**
+ * NES 2.0 ROM size, in bytes.
+ * If the MSB nibble is $F, the LSB is an exponent-multiplier: EEEE EEMM,
+ * and the size is 2^E * (MM * 2 + 1). Otherwise the size is a 12-bit count of pages.
+ * Returns None if the size doesn't fit in a usize.
+ */
+fn nes_2_rom_size(lsb: u8, msb_nibble: u8, page_size: usize) -> Option<usize> {
+    if msb_nibble == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0b11) as usize * 2 + 1;
+        2usize.checked_pow(exponent)?.checked_mul(multiplier)
+    } else {
+        (((msb_nibble as usize) << 8) | lsb as usize).checked_mul(page_size)
+    }
+}
+
+/**
+ * NES 2.0 RAM size, in bytes, from a shift count: 64 << shift_count, or 0 if shift_count is 0
+ */
+fn nes_2_ram_size(shift_count: u8) -> usize {
+    if shift_count == 0 {
+        0
+    } else {
+        64 << shift_count
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::rom::{
+        ConsoleType, Mapper, Mirroring, Rom, RomError, RomFormat, Timing, I_NES_IDENTIFIER_BYTES,
+    };
+
+    /**
+     * Builds a ROM file from the given header bytes 4-15, followed by the
+     * PRG-ROM and CHR-ROM sizes it declares
+     */
+    fn new_rom_bytes(header: [u8; 12], program_rom_size: usize, chr_rom_size: usize) -> Vec<u8> {
+        let mut rom_bytes = I_NES_IDENTIFIER_BYTES.to_vec();
+        rom_bytes.extend(header);
+        rom_bytes.extend(vec![0; program_rom_size + chr_rom_size]);
+        rom_bytes
+    }
+
+    #[test]
+    fn test_i_nes_header() {
+        let mut header = [0; 12];
+        header[0] = 2; // PRG-ROM banks
+        header[1] = 1; // CHR-ROM banks
+        header[2] = 0b0001_0011; // mapper D3..D0 = 1, battery, vertical mirroring
+        let rom = Rom::new(&new_rom_bytes(header, 0x8000, 0x2000)).unwrap();
+
+        assert_eq!(rom.format, RomFormat::INes);
+        assert_eq!(rom.mapper, Mapper::One);
+        assert_eq!(rom.mirroring, Mirroring::Vertical);
+        assert!(rom.has_battery);
+        assert_eq!(rom.program_rom.len(), 0x8000);
+        assert_eq!(rom.chr_rom.len(), 0x2000);
+        assert_eq!(rom.program_nvram_size, 0x2000);
+        assert_eq!(rom.timing, Timing::Ntsc);
+    }
+
+    #[test]
+    fn test_nes_2_header() {
+        let mut header = [0; 12];
+        header[0] = 0x02; // PRG-ROM size LSB
+        header[2] = 0b0100_0000; // mapper D3..D0 = 4
+        header[3] = 0b0000_1001; // NES 2.0, Vs. System
+        header[4] = 0b0001_0000; // submapper 1
+        header[5] = 0b0000_0001; // PRG-ROM size MSB = 1
+        header[6] = 0b0111_0000; // PRG-NVRAM = 64 << 7
+        header[7] = 0b0000_0111; // CHR-RAM = 64 << 7
+        header[8] = 0b0000_0001; // PAL
+        header[11] = 0x01; // standard controllers
+        let rom = Rom::new(&new_rom_bytes(header, 0x102 * 0x4000, 0)).unwrap();
+
+        assert_eq!(rom.format, RomFormat::Nes2);
+        assert_eq!(rom.mapper, Mapper::Four);
+        assert_eq!(rom.mapper_number, 4);
+        assert_eq!(rom.submapper, 1);
+        assert_eq!(rom.program_rom.len(), 0x102 * 0x4000);
+        assert_eq!(rom.program_ram_size, 0);
+        assert_eq!(rom.program_nvram_size, 0x2000);
+        assert_eq!(rom.chr_ram_size, 0x2000);
+        assert_eq!(rom.timing, Timing::Pal);
+        assert_eq!(rom.console_type, ConsoleType::VsSystem);
+        assert_eq!(rom.expansion_device, 1);
+    }
+
+    #[test]
+    fn test_nes_2_exponent_multiplier_rom_size() {
+        let mut header = [0; 12];
+        header[0] = 0b0011_1001; // 2^14 * (1 * 2 + 1) = 48KB
+        header[3] = 0b0000_1000; // NES 2.0
+        header[5] = 0x0F; // PRG-ROM size uses exponent-multiplier
+        let rom = Rom::new(&new_rom_bytes(header, 0xC000, 0)).unwrap();
+
+        assert_eq!(rom.program_rom.len(), 0xC000);
+    }
+
+    #[test]
+    fn test_trainer() {
+        let mut header = [0; 12];
+        header[0] = 1; // PRG-ROM banks
+        header[2] = 0b0000_0100; // trainer
+        let mut rom_bytes = new_rom_bytes(header, 0, 0);
+        rom_bytes.extend(vec![0xAA; 512]);
+        rom_bytes.extend(vec![0xBB; 0x4000]);
+        let rom = Rom::new(&rom_bytes).unwrap();
+
+        assert_eq!(rom.trainer, Some(vec![0xAA; 512]));
+        assert_eq!(rom.program_rom, vec![0xBB; 0x4000]);
+    }
+
+    #[test]
+    fn test_four_screen_mirroring() {
+        let mut header = [0; 12];
+        header[0] = 1; // PRG-ROM banks
+        header[2] = 0b0000_1001; // four-screen, vertical mirroring
+        let rom = Rom::new(&new_rom_bytes(header, 0x4000, 0)).unwrap();
+
+        assert_eq!(rom.mirroring, Mirroring::FourScreen);
+        assert_eq!(rom.trainer, None);
+    }
+
+    #[test]
+    fn test_invalid_rom_files() {
+        assert_eq!(
+            Rom::new(&[0x4E, 0x45, 0x53]),
+            Err(RomError::TooShort { actual: 3 })
+        );
+        assert_eq!(Rom::new(&[0; 16]), Err(RomError::BadMagic));
+
+        let mut header = [0; 12];
+        header[3] = 0b0000_0100;
+        assert_eq!(
+            Rom::new(&new_rom_bytes(header, 0, 0)),
+            Err(RomError::UnsupportedVersion)
+        );
+
+        let mut header = [0; 12];
+        header[2] = 0b0101_0000;
+        assert_eq!(
+            Rom::new(&new_rom_bytes(header, 0, 0)),
+            Err(RomError::UnsupportedMapper(5))
+        );
+
+        let mut header = [0; 12];
+        header[0] = 2;
+        header[1] = 1;
+        assert_eq!(
+            Rom::new(&new_rom_bytes(header, 0x4000, 0)),
+            Err(RomError::Truncated {
+                expected: 16 + 0x8000 + 0x2000,
+                actual: 16 + 0x4000
+            })
+        );
+
+        assert_eq!(
+            Rom::new(&new_rom_bytes([0; 12], 0, 0)),
+            Err(RomError::NoProgramRom)
+        );
+
+        // 2^10 bytes of PRG-ROM
+        let mut header = [0; 12];
+        header[0] = 0b0010_1000;
+        header[3] = 0b0000_1000;
+        header[5] = 0x0F;
+        assert_eq!(
+            Rom::new(&new_rom_bytes(header, 0x400, 0)),
+            Err(RomError::PartialProgramBank {
+                size: 0x400,
+                bank_size: 0x4000
+            })
+        );
+
+        // AxROM with 16KB of PRG-ROM
+        let mut header = [0; 12];
+        header[0] = 1;
+        header[2] = 0b0111_0000;
+        assert_eq!(
+            Rom::new(&new_rom_bytes(header, 0x4000, 0)),
+            Err(RomError::PartialProgramBank {
+                size: 0x4000,
+                bank_size: 0x8000
+            })
+        );
+
+        // MMC3 with 2^9 bytes of CHR-ROM
+        let mut header = [0; 12];
+        header[0] = 2;
+        header[1] = 0b0010_0100;
+        header[2] = 0b0100_0000;
+        header[3] = 0b0000_1000;
+        header[5] = 0xF0;
+        assert_eq!(
+            Rom::new(&new_rom_bytes(header, 0x8000, 0x200)),
+            Err(RomError::PartialChrBank {
+                size: 0x200,
+                bank_size: 0x400
+            })
+        );
+
+        // 2^63 * 7 bytes of PRG-ROM
+        let mut header = [0; 12];
+        header[0] = 0xFF;
+        header[3] = 0b0000_1000;
+        header[5] = 0x0F;
+        assert_eq!(
+            Rom::new(&new_rom_bytes(header, 0, 0)),
+            Err(RomError::TooLarge)
+        );
+
+        // 2^63 bytes each of PRG-ROM and CHR-ROM
+        let mut header = [0; 12];
+        header[0] = 0b1111_1100;
+        header[1] = 0b1111_1100;
+        header[3] = 0b0000_1000;
+        header[5] = 0xFF;
+        assert_eq!(
+            Rom::new(&new_rom_bytes(header, 0, 0)),
+            Err(RomError::TooLarge)
+        );
+    }
 }
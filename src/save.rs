@@ -0,0 +1,76 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{cartridge::Cartridge, util::Error};
+
+/**
+ * The save file for a ROM lives next to it, e.g. roms/zelda.nes -> roms/zelda.sav
+ */
+pub fn save_path(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("sav")
+}
+
+/**
+ * Loads the save file into the cartridge's battery-backed RAM.
+ * Does nothing if the cartridge has no battery, or there's no save file yet.
+ */
+pub fn load(cartridge: &mut dyn Cartridge, path: &Path) -> Result<(), Error> {
+    let Some(battery_ram) = cartridge.battery_ram() else {
+        return Ok(());
+    };
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let save_bytes = fs::read(path)?;
+    let length = save_bytes.len().min(battery_ram.len());
+    battery_ram[..length].copy_from_slice(&save_bytes[..length]);
+    Ok(())
+}
+
+/**
+ * Writes the cartridge's battery-backed RAM to the save file.
+ * Does nothing if the cartridge has no battery.
+ */
+pub fn save(cartridge: &mut dyn Cartridge, path: &Path) -> Result<(), Error> {
+    if let Some(battery_ram) = cartridge.battery_ram() {
+        fs::write(path, battery_ram)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::{env, fs};
+
+    use crate::{
+        cartridge,
+        rom::{Mapper, Rom},
+    };
+
+    #[test]
+    fn test_battery_ram_round_trip() {
+        let path = env::temp_dir().join("nes_test_battery_ram_round_trip.sav");
+        let new_rom = || Rom {
+            mapper: Mapper::One,
+            has_battery: true,
+            program_ram_size: 0,
+            program_nvram_size: 0x2000,
+            ..Rom::new_empty()
+        };
+
+        let mut cartridge = cartridge::new(new_rom());
+        cartridge.cpu_write(0x6000, 0x66);
+        cartridge.cpu_write(0x7FFF, 0x77);
+        super::save(cartridge.as_mut(), &path).unwrap();
+
+        let mut cartridge = cartridge::new(new_rom());
+        super::load(cartridge.as_mut(), &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(cartridge.cpu_read(0x6000), 0x66);
+        assert_eq!(cartridge.cpu_read(0x7FFF), 0x77);
+    }
+}
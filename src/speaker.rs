@@ -0,0 +1,62 @@
+use std::{mem, thread, time::Duration};
+
+use sdl2::{
+    audio::{AudioQueue, AudioSpecDesired},
+    Sdl,
+};
+
+use nes::util::Error;
+
+/**
+ * Samples per buffer SDL hands to the audio device
+ */
+const BUFFER_SAMPLES: u16 = 1024;
+/**
+ * Audio queued ahead of the device. Enough to ride out a slow frame, without
+ * noticeable latency.
+ */
+const QUEUED_MILLIS: u32 = 60;
+
+pub struct Speaker {
+    queue: AudioQueue<f32>,
+    max_queued_bytes: u32,
+}
+
+impl Speaker {
+    pub fn new(sdl_context: &Sdl, sample_rate: u32) -> Result<Self, Error> {
+        let audio_subsystem = sdl_context.audio()?;
+        let spec = AudioSpecDesired {
+            freq: Some(sample_rate as i32),
+            channels: Some(1),
+            samples: Some(BUFFER_SAMPLES),
+        };
+        let queue = audio_subsystem.open_queue::<f32, _>(None, &spec)?;
+        queue.resume();
+
+        // The device may not support the sample rate asked for
+        let queued_samples = queue.spec().freq as u32 * QUEUED_MILLIS / 1000;
+        let max_queued_bytes = queued_samples * mem::size_of::<f32>() as u32;
+
+        Ok(Speaker {
+            queue,
+            max_queued_bytes,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.queue.spec().freq as u32
+    }
+
+    /**
+     * Queues the samples for playback. Waits while the queue is full, so the
+     * emulation runs at the speed audio plays at, and the queue never runs dry.
+     */
+    pub fn play(&mut self, samples: &[f32]) -> Result<(), Error> {
+        self.queue.queue_audio(samples)?;
+        while self.queue.size() > self.max_queued_bytes {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        Ok(())
+    }
+}
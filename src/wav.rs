@@ -0,0 +1,163 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::util::Error;
+
+const HEADER_SIZE: u32 = 44;
+const CHANNELS: u16 = 1;
+const BITS_PER_SAMPLE: u16 = 16;
+const BYTES_PER_SAMPLE: u32 = BITS_PER_SAMPLE as u32 / 8;
+// Offsets of the sizes that are only known once all the samples are written
+const RIFF_SIZE_OFFSET: u64 = 4;
+const DATA_SIZE_OFFSET: u64 = 40;
+
+/**
+ * Streams mono samples to a 16-bit PCM WAV file, e.g. audio.take_samples()
+ * after each frame. The header's sizes are filled in by finish().
+ */
+#[derive(Debug)]
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    data_size: u32,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create(path: &Path, sample_rate: u32) -> Result<Self, Error> {
+        WavWriter::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /**
+     * Writes the header, with empty sizes
+     */
+    pub fn new(mut writer: W, sample_rate: u32) -> Result<Self, Error> {
+        let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+        let byte_rate = sample_rate * block_align as u32;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // PCM
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&CHANNELS.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        Ok(WavWriter {
+            writer,
+            data_size: 0,
+        })
+    }
+
+    /**
+     * Appends samples in [-1, 1]. Louder samples are clipped.
+     */
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<(), Error> {
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_size += samples.len() as u32 * BYTES_PER_SAMPLE;
+        Ok(())
+    }
+
+    /**
+     * Fills in the header's sizes.
+     * Returns the underlying writer.
+     */
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.writer.seek(SeekFrom::Start(RIFF_SIZE_OFFSET))?;
+        self.writer
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(DATA_SIZE_OFFSET))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::io::Cursor;
+
+    use crate::{console::Console, region::Region, rom::Rom, wav::WavWriter};
+
+    #[test]
+    fn test_wav_header() {
+        let mut wav = WavWriter::new(Cursor::new(vec![]), 44100).unwrap();
+        wav.write_samples(&[0.0, 1.0, -1.0, 2.0]).unwrap();
+        let bytes = wav.finish().unwrap().into_inner();
+
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(bytes[4..8], (36u32 + 8).to_le_bytes());
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(bytes[24..28], 44100u32.to_le_bytes());
+        assert_eq!(bytes[28..32], 88200u32.to_le_bytes());
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(bytes[40..44], 8u32.to_le_bytes());
+
+        let samples: Vec<i16> = bytes[44..]
+            .chunks(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        assert_eq!(samples, vec![0, i16::MAX, -i16::MAX, i16::MAX]);
+    }
+
+    #[test]
+    fn test_record_headless() {
+        // Plays a pulse wave, then loops
+        let mut program = vec![
+            0xA9, 0x01, // LDA #$01
+            0x8D, 0x15, 0x40, // STA $4015
+            0xA9, 0xBF, // LDA #$BF
+            0x8D, 0x00, 0x40, // STA $4000
+            0xA9, 0xFD, // LDA #$FD
+            0x8D, 0x02, 0x40, // STA $4002
+            0xA9, 0x08, // LDA #$08
+            0x8D, 0x03, 0x40, // STA $4003
+            0x4C, 0x14, 0x80, // JMP $8014
+        ];
+        program.resize(0x4000, 0xEA);
+        program[0x3FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+        let mut rom_bytes = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0];
+        rom_bytes.resize(16, 0);
+        rom_bytes.extend(program);
+        rom_bytes.extend(vec![0; 0x2000]);
+
+        let record = || {
+            let rom = Rom::new(&rom_bytes).unwrap();
+            let mut console = Console::new(rom, Region::Ntsc);
+            let mut wav = WavWriter::new(Cursor::new(vec![]), 44100).unwrap();
+            for _ in 0..3 {
+                console.run_frame().unwrap();
+                wav.write_samples(&console.apu.audio.take_samples())
+                    .unwrap();
+            }
+            wav.finish().unwrap().into_inner()
+        };
+
+        let bytes = record();
+        // About 3 frames of samples, some of them not silent
+        let samples = (bytes.len() - 44) / 2;
+        assert!((2000..2300).contains(&samples));
+        assert!(bytes[44..].iter().any(|&byte| byte != 0));
+
+        // The same every time
+        assert_eq!(record(), bytes);
+    }
+}